@@ -0,0 +1,73 @@
+//! Measures the cost of copying a [Game], which the searches do at the start
+//! of every playout. The Game holds its move history, so moves can be undone,
+//! which makes it much larger than the boards alone.
+//!
+//! Run with `cargo bench --bench game`.
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::hint::black_box;
+use std::mem::size_of;
+use std::time::Duration;
+use std::time::Instant;
+use ultimate_tic_tac_toe::playout::PlayoutPolicy;
+use ultimate_tic_tac_toe::playout::Uniform;
+use ultimate_tic_tac_toe::Game;
+use ultimate_tic_tac_toe::MegaBoard;
+use ultimate_tic_tac_toe::PossibleMoves;
+
+/// How long to run each measurement.
+const DURATION: Duration = Duration::from_secs(2);
+
+/// The number of copies between checks of the time.
+const BATCH: usize = 10_000;
+
+/// Returns the number of copies of the value made per second.
+fn copies<T: Copy>(value: &T) -> f64 {
+    let start = Instant::now();
+    let mut count = 0;
+    while start.elapsed() < DURATION {
+        for _ in 0..BATCH {
+            black_box(*black_box(value));
+        }
+        count += BATCH;
+    }
+
+    count as f64 / start.elapsed().as_secs_f64()
+}
+
+/// Returns the number of uniformly random playouts from the game per second,
+/// copying the game at the start of each, as the searches do.
+fn playouts(game: &Game) -> f64 {
+    let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+    let start = Instant::now();
+    let mut count = 0;
+    while start.elapsed() < DURATION {
+        let mut g = *black_box(game);
+        while g.playable() {
+            let (board_pos, square_pos) = Uniform.choose(r, &g);
+            g.play(board_pos, square_pos).unwrap();
+        }
+        black_box(g);
+        count += 1;
+    }
+
+    count as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let game = Game::default();
+
+    println!(
+        "Game {} bytes, of which MegaBoard {} bytes",
+        size_of::<Game>(),
+        size_of::<MegaBoard>()
+    );
+    println!("{:<10} {:>14.0} copies/s", "Game", copies(&game));
+    println!(
+        "{:<10} {:>14.0} copies/s",
+        "MegaBoard",
+        copies(game.megaboard())
+    );
+    println!("{:<10} {:>14.0} playouts/s", "Playouts", playouts(&game));
+}
@@ -0,0 +1,101 @@
+//! Compares the playout policies, by how many playouts they run per second,
+//! and how often a Monte Carlo AI using them beats one using uniformly random
+//! playouts with the same number of playouts.
+//!
+//! Run with `cargo bench --bench playout`.
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+use std::time::Instant;
+use ultimate_tic_tac_toe::ai::Budget;
+use ultimate_tic_tac_toe::ai::MonteCarloAI;
+use ultimate_tic_tac_toe::ai::AI;
+use ultimate_tic_tac_toe::playout::*;
+use ultimate_tic_tac_toe::Game;
+use ultimate_tic_tac_toe::PossibleMoves;
+use ultimate_tic_tac_toe::Square;
+use ultimate_tic_tac_toe::Winner;
+
+/// How long to measure the speed of each policy.
+const DURATION: Duration = Duration::from_secs(2);
+
+/// The number of games played against the uniform policy.
+const GAMES: usize = 40;
+
+/// The number of playouts each AI does per move.
+const PLAYOUTS: usize = 500;
+
+/// Returns the number of games played per second using the policy.
+fn speed<P: PlayoutPolicy>(policy: &P) -> f64 {
+    let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < DURATION {
+        let mut g = Game::default();
+        while g.playable() {
+            let (board_pos, square_pos) = policy.choose(r, &g);
+            g.play(board_pos, square_pos).unwrap();
+        }
+        runs += 1;
+    }
+
+    runs as f64 / start.elapsed().as_secs_f64()
+}
+
+/// Returns the number of wins, draws and losses against the uniform policy,
+/// playing first in half the games.
+fn strength<P: PlayoutPolicy + Copy>(policy: P) -> (usize, usize, usize) {
+    let r = &mut ChaCha8Rng::seed_from_u64(0);
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+
+    for game in 0..GAMES {
+        let mut ai = MonteCarloAI::with_budget(Budget::Playouts(PLAYOUTS))
+            .with_solver(0, 0)
+            .with_policy(policy);
+        let mut uniform = MonteCarloAI::with_budget(Budget::Playouts(PLAYOUTS)).with_solver(0, 0);
+
+        let me = if game % 2 == 0 { Square::O } else { Square::X };
+
+        let mut g = Game::default();
+        while g.playable() {
+            let (board_pos, square_pos) = if g.current_player() == me {
+                ai.choose(&mut *r, &g).unwrap().pos()
+            } else {
+                uniform.choose(&mut *r, &g).unwrap().pos()
+            };
+            g.play(board_pos, square_pos).unwrap();
+        }
+
+        match g.winner() {
+            Square::None => draws += 1,
+            winner if winner == me => wins += 1,
+            _ => losses += 1,
+        }
+    }
+
+    (wins, draws, losses)
+}
+
+fn bench<P: PlayoutPolicy + Copy>(name: &str, policy: P) {
+    let speed = speed(&policy);
+    let (wins, draws, losses) = strength(policy);
+
+    println!(
+        "{:<16} {:>10.0} games/s {:>4} wins {:>4} draws {:>4} losses",
+        name, speed, wins, draws, losses
+    );
+}
+
+fn main() {
+    println!(
+        "Against uniform playouts, {} games with {} playouts per move",
+        GAMES, PLAYOUTS
+    );
+
+    bench("Uniform", Uniform);
+    bench("TakeWins", TakeWins);
+    bench("BlockWins", BlockWins);
+    bench("AvoidFreeMoves", AvoidFreeMoves);
+    bench("Greedy", Greedy);
+}
@@ -0,0 +1,33 @@
+//! Generates an opening book by searching every position in the first few
+//! moves of the game.
+//!
+//! Run with `cargo run --release --example book -- <path> [plies] [seconds]`.
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+use ultimate_tic_tac_toe::book::Book;
+use ultimate_tic_tac_toe::mcts::MctsAI;
+use ultimate_tic_tac_toe::Game;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <path> [plies] [seconds]", args[0]);
+        std::process::exit(1);
+    }
+
+    let path = &args[1];
+    let plies = args.get(2).map_or(2, |s| s.parse().expect("plies"));
+    let seconds = args.get(3).map_or(10, |s| s.parse().expect("seconds"));
+
+    let rng = ChaCha8Rng::seed_from_u64(0);
+    let mut ai = MctsAI::new(Duration::from_secs(seconds));
+    let book = Book::generate(&mut ai, rng, &Game::default(), plies).expect("generate book");
+
+    let file = File::create(path).expect("create book file");
+    book.write(BufWriter::new(file)).expect("write book");
+    println!("Wrote {} positions to {}", book.len(), path);
+}
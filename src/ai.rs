@@ -1,5 +1,15 @@
 use instant::Instant; // Works on wasm instead of use std::time::Instant;
+use crate::error::AiError;
 use crate::monte_carlo::MegaBoardStats;
+use crate::monte_carlo::Stats;
+use crate::playout::PlayoutPolicy;
+use crate::playout::Uniform;
+use crate::solver;
+use crate::solver::Solution;
+use crate::solver::Solver;
+use crate::solver::Value;
+use crate::transposition::TableStats;
+use crate::transposition::TranspositionTable;
 use crate::Game;
 use crate::PossibleMoves;
 use crate::Square;
@@ -7,8 +17,125 @@ use crate::Winner;
 use core::time::Duration;
 use rand::RngCore;
 
+/// How much searching to do before picking a move.
+///
+/// A time limit depends on the speed and load of the machine, so only the
+/// other budgets give the same move for the same seed.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Budget {
+    /// Search until the time has passed.
+    Time(Duration),
+
+    /// Search until this many games have been played out.
+    Playouts(usize),
+
+    /// Search until this many nodes have been visited. For tree searches this
+    /// is the number of nodes added to the tree, otherwise it is the number of
+    /// positions played through.
+    Nodes(usize),
+
+    /// Search until whichever limit is reached first.
+    First {
+        time: Duration,
+        playouts: usize,
+        nodes: usize,
+    },
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::Time(Duration::new(1, 0))
+    }
+}
+
+impl Budget {
+    /// Returns true once a search started at `start`, which has done this many
+    /// playouts and visited this many nodes, has used up the budget.
+    pub fn exhausted(&self, start: Instant, playouts: usize, nodes: usize) -> bool {
+        match *self {
+            Budget::Time(time) => start.elapsed() > time,
+            Budget::Playouts(limit) => playouts >= limit,
+            Budget::Nodes(limit) => nodes >= limit,
+            Budget::First {
+                time,
+                playouts: playouts_limit,
+                nodes: nodes_limit,
+            } => start.elapsed() > time || playouts >= playouts_limit || nodes >= nodes_limit,
+        }
+    }
+}
+
+/// How good a move is for the player making it. Each kind of AI measures it
+/// differently, so only scores of the same kind can be compared.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Score {
+    /// The average result of sampled games, from 0 for a loss to 1 for a win,
+    /// counting draws as half a win.
+    Expected(f64),
+
+    /// A heuristic evaluation, where higher is better.
+    Evaluation(i32),
+
+    /// The outcome with best play, proved by searching to the end of the game.
+    Solved(Value),
+}
+
+/// What an AI found while searching for a move.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SearchInfo {
+    pub score: Score,
+
+    /// The best moves for both players, starting with the chosen move. Only
+    /// as long as the AI could work out.
+    pub pv: Vec<(usize, usize)>,
+
+    /// The number of nodes searched, counted as for the [Budget].
+    pub nodes: usize,
+}
+
+/// A move picked by an AI.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Move {
+    pub board_pos: usize,
+    pub square_pos: usize,
+
+    /// What the search found, or None if the AI doesn't search.
+    pub info: Option<SearchInfo>,
+}
+
+impl Move {
+    pub fn new(board_pos: usize, square_pos: usize) -> Self {
+        Move {
+            board_pos,
+            square_pos,
+            info: None,
+        }
+    }
+
+    pub fn with_info(mut self, info: SearchInfo) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Returns the (board_pos, square_pos) of the move.
+    pub fn pos(&self) -> (usize, usize) {
+        (self.board_pos, self.square_pos)
+    }
+}
+
 pub trait AI {
-    fn choose<R: RngCore>(&mut self, r: R, g: &Game) -> (usize, usize);
+    /// Returns the move to play, or [AiError::NoLegalMoves] if the game is over.
+    fn choose<R: RngCore>(&mut self, r: R, g: &Game) -> Result<Move, AiError>;
+}
+
+/// An AI that can search while waiting for the opponent to move, keeping the
+/// work for its next [AI::choose].
+pub trait Ponder: AI {
+    /// Searches the position, which is usually the opponent to move, for at
+    /// most `playouts` playouts. Call repeatedly to keep searching, and stop
+    /// at any point, as each call leaves the search ready to be continued or
+    /// used. Returns the number of playouts done.
+    fn ponder<R: RngCore>(&mut self, r: &mut R, game: &Game, playouts: usize) -> usize;
 }
 
 /// Picks the next move completely at random.
@@ -16,103 +143,421 @@ pub trait AI {
 pub struct RandomAI {}
 
 impl AI for RandomAI {
-    fn choose<R: RngCore>(&mut self, mut r: R, g: &Game) -> (usize, usize) {
+    fn choose<R: RngCore>(&mut self, mut r: R, g: &Game) -> Result<Move, AiError> {
+        if !g.playable() {
+            return Err(AiError::NoLegalMoves);
+        }
+
         let board_pos = g.choose(&mut r).unwrap();
         let square_pos = g[board_pos].choose(&mut r).unwrap();
 
-        (board_pos, square_pos)
+        Ok(Move::new(board_pos, square_pos))
     }
 }
 
+/// The number of moves into each playout whose positions are recorded in the
+/// transposition table.
+const RECORDED_PLIES: usize = 4;
+
+/// Positions with fewer open squares than this are solved instead of sampled.
+pub const DEFAULT_SOLVER_THRESHOLD: usize = 20;
+
+/// The most nodes searched trying to solve a position, before falling back to
+/// sampling.
+pub const DEFAULT_SOLVER_NODES: usize = 100_000;
+
 // Picks the next move completely based on MonteCarlo simulation.
-#[derive(Default)]
-pub struct MonteCarloAI {
-    time_limit: Duration,
+pub struct MonteCarloAI<P: PlayoutPolicy = Uniform> {
+    budget: Budget,
+
+    /// Picks the moves after the first in each playout.
+    policy: P,
+
     pub last_results: MegaBoardStats,
+
+    /// The solution found for the last position, if it was solved.
+    pub last_solution: Option<Solution>,
+
+    solver_threshold: usize,
+    solver_nodes: usize,
+    solver_table_size: usize,
+
+    /// Created the first time a position is solved, so its table is only
+    /// allocated by AIs that get near the end of a game.
+    solver: Option<Solver>,
+
+    /// Results for the positions early in each playout, kept between turns.
+    /// The results for each move are those of every playout that reached the
+    /// same position, by any order of moves, in this or earlier searches.
+    table: Option<TranspositionTable<Stats>>,
 }
 
-impl MonteCarloAI {
+impl MonteCarloAI<Uniform> {
     pub fn new(time_limit: Duration) -> Self {
+        Self::with_budget(Budget::Time(time_limit))
+    }
+
+    pub fn with_budget(budget: Budget) -> Self {
         MonteCarloAI {
-            time_limit,
+            budget,
+            policy: Uniform,
+            last_results: MegaBoardStats::default(),
+            last_solution: None,
+            solver_threshold: DEFAULT_SOLVER_THRESHOLD,
+            solver_nodes: DEFAULT_SOLVER_NODES,
+            solver_table_size: solver::DEFAULT_TABLE_SIZE,
+            solver: None,
+            table: None,
+        }
+    }
+}
+
+impl<P: PlayoutPolicy> MonteCarloAI<P> {
+    /// Sets how moves are picked during playouts.
+    pub fn with_policy<Q: PlayoutPolicy>(self, policy: Q) -> MonteCarloAI<Q> {
+        MonteCarloAI {
+            budget: self.budget,
+            policy,
+            last_results: self.last_results,
+            last_solution: self.last_solution,
+            solver_threshold: self.solver_threshold,
+            solver_nodes: self.solver_nodes,
+            solver_table_size: self.solver_table_size,
+            solver: self.solver,
+            table: self.table,
+        }
+    }
+
+    /// Solves positions with fewer than `threshold` open squares, searching at
+    /// most `node_limit` nodes. A threshold of 0 never solves.
+    pub fn with_solver(mut self, threshold: usize, node_limit: usize) -> Self {
+        self.solver_threshold = threshold;
+        self.solver_nodes = node_limit;
+        self.solver = None;
+        self
+    }
+
+    /// Sets the size of the solver's transposition table in bytes.
+    pub fn with_solver_table_size(mut self, size: usize) -> Self {
+        self.solver_table_size = size;
+        self.solver = None;
+        self
+    }
+
+    /// Sets the size of the transposition table in bytes, with 0 for no table.
+    pub fn with_table_size(mut self, size: usize) -> Self {
+        self.table = if size == 0 {
+            None
+        } else {
+            Some(TranspositionTable::new(size))
+        };
+        self
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
 
-            ..Default::default()
+    /// Returns how the transposition table was used in the last search.
+    pub fn table_stats(&self) -> Option<TableStats> {
+        self.table.as_ref().map(|table| table.stats())
+    }
+
+    /// Returns the solution to the game, if it's small enough to solve.
+    fn solve(&mut self, game: &Game) -> Option<Solution> {
+        if game.open_squares() >= self.solver_threshold {
+            return None;
         }
+
+        let (node_limit, size) = (self.solver_nodes, self.solver_table_size);
+        self.solver
+            .get_or_insert_with(|| Solver::with_table_size(node_limit, size))
+            .solve(game)
     }
 }
 
-impl AI for MonteCarloAI {
-    // TODO Change return value to Result<>
-    fn choose<R: RngCore>(&mut self, mut r: R, game: &Game) -> (usize, usize) {
-        let mut stats = MegaBoardStats::default();
+impl Default for MonteCarloAI {
+    fn default() -> Self {
+        Self::with_budget(Budget::default())
+    }
+}
 
-        let mut now = Instant::now();
+impl<P: PlayoutPolicy> AI for MonteCarloAI<P> {
+    fn choose<R: RngCore>(&mut self, mut r: R, game: &Game) -> Result<Move, AiError> {
+        if !game.playable() {
+            return Err(AiError::NoLegalMoves);
+        }
 
-        let start = now;
-        let me = game.current_player();
+        self.last_solution = self.solve(game);
+        if let Some(solution) = &self.last_solution {
+            self.last_results = MegaBoardStats::default();
 
-        loop {
-            let mut g = game.clone(); // Reset
-            assert!(g.playable());
+            let (board_pos, square_pos) = solution.pv[0];
+            return Ok(Move::new(board_pos, square_pos).with_info(SearchInfo {
+                score: Score::Solved(solution.value),
+                pv: solution.pv.clone(),
+                nodes: self.solver.as_ref().map_or(0, |solver| solver.nodes()),
+            }));
+        }
 
-            // Pick a random next move.
-            let board_pos = g.choose(&mut r).unwrap();
-            let square_pos = g[board_pos].choose(&mut r).unwrap();
+        let mut stats = MegaBoardStats::default();
 
-            // Play this first move
-            g.play(board_pos, square_pos)
-                .expect("valid first move play");
+        let start = Instant::now();
+        let mut nodes = 0;
 
-            // Now keep playing randomly
-            while g.playable() {
-                // TODO sample based on the paths that seem best
-                let board_pos = g.choose(&mut r).unwrap();
-                let square_pos = g[board_pos].choose(&mut r).unwrap();
+        if let Some(table) = &mut self.table {
+            table.new_search();
+        }
 
-                g.play(board_pos, square_pos).expect("valid play");
-            }
+        loop {
+            nodes += playout(&mut r, game, &self.policy, &mut stats, self.table.as_mut());
 
-            let mut stat = &mut stats.board[board_pos][square_pos];
-            if g.winner() == me {
-                stat.wins += 1;
-            } else if g.winner() != Square::None {
-                stat.loses += 1;
+            if self.budget.exhausted(start, stats.runs, nodes) {
+                break;
             }
+        }
 
-            stat.totals += 1;
-            stats.runs += 1;
+        // Include the results of playouts that transposed into each move.
+        if let Some(table) = &mut self.table {
+            let runs = stats.runs;
+            for (board_pos, square_pos) in game.legal_moves() {
+                let mut g = *game;
+                g.play(board_pos, square_pos).expect("legal move");
 
-            now = Instant::now();
-            if (now - start) > self.time_limit {
-                break;
+                if let Some((_, stat)) = table.probe(g.hash()) {
+                    stats.board[board_pos][square_pos] = stat;
+                }
             }
+            stats.runs = runs.max(stats.totals().totals);
         }
 
-        let best = stats.best();
+        // Every playout starts with a legal move, so this only falls back
+        // if no playouts were run.
+        let (board_pos, square_pos) = stats
+            .best()
+            .or_else(|| game.legal_moves().next())
+            .ok_or(AiError::NoLegalMoves)?;
+        let info = SearchInfo {
+            score: Score::Expected(stats.board[board_pos][square_pos].expected()),
+            pv: vec![(board_pos, square_pos)],
+            nodes,
+        };
 
         // Record the results
         self.last_results = stats;
 
-        best
+        Ok(Move::new(board_pos, square_pos).with_info(info))
+    }
+}
+
+/// Plays a random first move followed by a game played by the policy, and
+/// records the result against the first move. If there is a table, the result
+/// is also recorded for the first few positions. Returns the number of moves
+/// played.
+pub(crate) fn playout<R: RngCore, P: PlayoutPolicy>(
+    r: &mut R,
+    game: &Game,
+    policy: &P,
+    stats: &mut MegaBoardStats,
+    mut table: Option<&mut TranspositionTable<Stats>>,
+) -> usize {
+    let mut g = *game; // Reset
+    assert!(g.playable());
+
+    let me = game.current_player();
+
+    // The hash of each recorded position, and the player who moved into it.
+    let mut recorded = [(0, Square::None); RECORDED_PLIES];
+    let mut plies = 0;
+
+    // Pick a random next move.
+    let board_pos = g.choose(r).unwrap();
+    let square_pos = g[board_pos].choose(r).unwrap();
+
+    // Play this first move
+    g.play(board_pos, square_pos)
+        .expect("valid first move play");
+    recorded[0] = (g.hash(), me);
+    plies += 1;
+
+    // Now keep playing with the policy
+    while g.playable() {
+        let (board_pos, square_pos) = policy.choose(r, &g);
+
+        let player = g.current_player();
+        g.play(board_pos, square_pos).expect("valid play");
+        if plies < RECORDED_PLIES {
+            recorded[plies] = (g.hash(), player);
+            plies += 1;
+        }
+    }
+
+    if let Some(table) = &mut table {
+        let winner = g.winner();
+        for (ply, &(hash, player)) in recorded[..plies].iter().enumerate() {
+            // Positions nearer the root are more valuable to keep.
+            let depth = (RECORDED_PLIES - ply) as u8;
+            table.update(hash, depth, |stat| {
+                if winner == player {
+                    stat.wins += 1;
+                } else if winner != Square::None {
+                    stat.loses += 1;
+                }
+                stat.totals += 1;
+            });
+        }
+    }
+
+    let stat = &mut stats.board[board_pos][square_pos];
+    if g.winner() == me {
+        stat.wins += 1;
+    } else if g.winner() != Square::None {
+        stat.loses += 1;
     }
+
+    stat.totals += 1;
+    stats.runs += 1;
+
+    g.turns() - game.turns()
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::ai::Budget;
     use crate::ai::MonteCarloAI;
+    use crate::ai::RandomAI;
+    use crate::ai::Score;
     use crate::ai::AI;
+    use crate::error::AiError;
+    use crate::playout::Greedy;
+    use crate::solver::Value;
     use crate::Game;
+    use crate::PossibleMoves;
+    use instant::Instant;
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
     use std::time::Duration;
 
     #[test]
     fn test_ai() {
-        let mut rng = ChaCha8Rng::from_rng(rand::thread_rng()).unwrap();
-
         let g = Game::default();
-        let mut ai = MonteCarloAI::new(Duration::new(1, 0));
 
-        ai.choose(&mut rng, &g);
+        // The same seed and budget always give the same move.
+        let mut moves = Vec::new();
+        for _ in 0..2 {
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut ai = MonteCarloAI::with_budget(Budget::Playouts(1000));
+
+            let m = ai.choose(&mut rng, &g).unwrap();
+            assert_eq!(ai.last_results.runs, 1000);
+
+            let info = m.info.clone().unwrap();
+            assert_eq!(info.pv, vec![m.pos()]);
+            assert!(info.nodes >= 1000);
+            moves.push(m);
+        }
+        assert_eq!(moves[0], moves[1]);
+    }
+
+    #[test]
+    fn game_over() {
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OOOXX..../..X....../X......../........./........./........./......... X - 17",
+        )
+        .unwrap();
+        assert!(!g.playable());
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        assert_eq!(
+            RandomAI::default().choose(&mut rng, &g),
+            Err(AiError::NoLegalMoves)
+        );
+        assert_eq!(
+            MonteCarloAI::default().choose(&mut rng, &g),
+            Err(AiError::NoLegalMoves)
+        );
+    }
+
+    #[test]
+    fn transpositions() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut g = Game::default();
+        let mut ai = MonteCarloAI::with_budget(Budget::Playouts(2000)).with_table_size(1024 * 1024);
+        assert_eq!(MonteCarloAI::default().table_stats(), None);
+
+        ai.choose(&mut rng, &g).unwrap();
+        let stats = ai.table_stats().unwrap();
+        assert!(stats.probes > 0);
+        assert_eq!(ai.last_results.totals().totals, 2000);
+
+        // The next turn starts with the results of playouts through its moves.
+        for m in [(4, 4), (4, 0)] {
+            g.play(m.0, m.1).unwrap();
+        }
+        ai.choose(&mut rng, &g).unwrap();
+        assert!(ai.last_results.runs > 2000);
+        assert!(ai.table_stats().unwrap().hits > 0);
+    }
+
+    #[test]
+    fn solver() {
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO.XX..../..X....../X......../........./........./........./......... O 2 16",
+        )
+        .unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        let mut ai = MonteCarloAI::with_budget(Budget::Playouts(10));
+        ai.choose(&mut rng, &g).unwrap();
+        assert!(ai.last_solution.is_none());
+
+        // The solver's table isn't allocated until a position is solved.
+        assert!(ai.solver.is_none());
+
+        // With enough squares, the win is proved rather than sampled.
+        let mut ai = MonteCarloAI::with_budget(Budget::Playouts(10))
+            .with_solver(81, 1_000_000)
+            .with_solver_table_size(64 * 1024);
+        let m = ai.choose(&mut rng, &g).unwrap();
+        assert_eq!(m.pos(), (2, 2));
+        assert_eq!(ai.last_solution.unwrap().value, Value::Win);
+
+        let info = m.info.unwrap();
+        assert_eq!(info.score, Score::Solved(Value::Win));
+        assert_eq!(info.pv, vec![(2, 2)]);
+        assert!(info.nodes > 0);
+    }
+
+    #[test]
+    fn policy() {
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../..OXX..../........./........./........./........./........./......... X 2 13",
+        )
+        .unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        // Winning board 2 also stops O completing the top row of boards.
+        let mut ai = MonteCarloAI::with_budget(Budget::Playouts(2000))
+            .with_solver(0, 0)
+            .with_policy(Greedy);
+        assert_eq!(ai.choose(&mut rng, &g).unwrap().pos(), (2, 5));
+    }
+
+    #[test]
+    fn budget() {
+        let start = Instant::now();
+        assert!(!Budget::Playouts(10).exhausted(start, 9, 100));
+        assert!(Budget::Playouts(10).exhausted(start, 10, 0));
+        assert!(Budget::Nodes(100).exhausted(start, 0, 100));
+        assert!(!Budget::Time(Duration::new(60, 0)).exhausted(start, 1000, 1000));
+
+        let first = Budget::First {
+            time: Duration::new(60, 0),
+            playouts: 10,
+            nodes: 100,
+        };
+        assert!(!first.exhausted(start, 9, 99));
+        assert!(first.exhausted(start, 10, 0));
+        assert!(first.exhausted(start, 0, 100));
     }
 }
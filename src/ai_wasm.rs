@@ -1,9 +1,17 @@
 use crate::monte_carlo::Stats;
 use std::time::Duration;
-use crate::ai::MonteCarloAI as WrappedMonteCarloAI;
+use crate::ai::Budget;
 use crate::ai::RandomAI as WrappedRandomAI;
+use crate::ai::Move;
+use crate::ai::Ponder;
 use crate::ai::AI;
+use crate::mcts::MctsAI;
+use crate::mcts::DEFAULT_EXPLORATION;
+use crate::mcts::PONDER_STEP;
+use crate::playout::Policy;
 use crate::Game;
+use crate::PossibleMoves;
+use instant::Instant; // Works on wasm instead of use std::time::Instant;
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 use wasm_bindgen::prelude::*;
@@ -24,50 +32,176 @@ impl From<(usize, usize)> for Pos {
     }
 }
 
+impl From<Move> for Pos {
+    fn from(m: Move) -> Self {
+        Pos {
+            board_pos: m.board_pos,
+            square_pos: m.square_pos,
+        }
+    }
+}
+
+/// How strongly the AI plays, as a preset of the other [AiOptions].
+#[wasm_bindgen]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+/// Options for the wasm AIs. Start from `new AiOptions()` for the defaults,
+/// or `AiOptions.with_difficulty(...)`, then change any of the fields.
+#[wasm_bindgen]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AiOptions {
+    /// The most time to search for each move, in milliseconds.
+    pub time_ms: f64,
+
+    /// The most playouts for each move, or 0 for no limit.
+    pub playouts: usize,
+
+    /// Seeds the random numbers, so games can be replayed. A random seed is
+    /// used if unset.
+    pub seed: Option<u32>,
+
+    /// The exploration constant for the tree search.
+    pub exploration: f64,
+
+    /// How moves are picked during playouts.
+    pub policy: Policy,
+}
+
+#[wasm_bindgen]
+impl AiOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> AiOptions {
+        AiOptions {
+            time_ms: 1000.0,
+            playouts: 0,
+            seed: None,
+            exploration: DEFAULT_EXPLORATION,
+            policy: Policy::Uniform,
+        }
+    }
+
+    /// Returns the options for the difficulty. Easier levels do fewer
+    /// playouts, so they finish quickly and make more mistakes.
+    pub fn with_difficulty(difficulty: Difficulty) -> AiOptions {
+        let (playouts, policy) = match difficulty {
+            Difficulty::Easy => (100, Policy::Uniform),
+            Difficulty::Medium => (1000, Policy::Uniform),
+            Difficulty::Hard => (10_000, Policy::Greedy),
+            Difficulty::Expert => (0, Policy::Greedy),
+        };
+
+        AiOptions {
+            playouts,
+            policy,
+            ..AiOptions::new()
+        }
+    }
+}
+
+impl AiOptions {
+    fn budget(&self) -> Budget {
+        let time = Duration::from_secs_f64(self.time_ms.max(0.0) / 1000.0);
+        match self.playouts {
+            0 => Budget::Time(time),
+            playouts => Budget::First {
+                time,
+                playouts,
+                nodes: usize::MAX,
+            },
+        }
+    }
+
+    fn rng(&self) -> ChaCha8Rng {
+        match self.seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed as u64),
+            None => ChaCha8Rng::from_rng(rand::thread_rng()).unwrap(),
+        }
+    }
+
+    fn mcts(&self) -> MctsAI<Policy> {
+        MctsAI::with_budget(self.budget())
+            .with_exploration(self.exploration)
+            .with_policy(self.policy)
+    }
+}
+
+impl Default for AiOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Implementation of the Random AI for wasm.
 #[wasm_bindgen]
 pub struct RandomAI {
     rng: ChaCha8Rng,
     ai: WrappedRandomAI,
-
-    pub blah: usize,
 }
 
 #[wasm_bindgen]
 impl RandomAI {
     #[wasm_bindgen(constructor)]
     pub fn new() -> RandomAI {
+        Self::with_options(&AiOptions::new())
+    }
+
+    /// Creates the AI, only using the seed from the options.
+    pub fn with_options(options: &AiOptions) -> RandomAI {
         RandomAI {
-            rng: ChaCha8Rng::from_rng(rand::thread_rng()).unwrap(),
+            rng: options.rng(),
             ai: Default::default(),
-            blah: 0,
         }
     }
 
-    pub fn choose(&mut self, g: &Game) -> Pos {
-        Pos::from(self.ai.choose(&mut self.rng, g))
+    /// Wrapper around [AI::choose] to return JsError.
+    pub fn choose(&mut self, g: &Game) -> Result<Pos, JsError> {
+        Ok(self.ai.choose(&mut self.rng, g)?.into())
     }
 }
 
-/// Implementation of the MonteCarlo AI for wasm.
+impl Default for RandomAI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implementation of the MonteCarlo AI for wasm. The search tree is kept
+/// between calls to choose, so calling it every turn builds on earlier results.
 #[wasm_bindgen]
 pub struct MonteCarloAI {
     rng: ChaCha8Rng,
-    ai: WrappedMonteCarloAI,
+    ai: MctsAI<Policy>,
 }
 
 #[wasm_bindgen]
 impl MonteCarloAI {
     #[wasm_bindgen(constructor)]
     pub fn new() -> MonteCarloAI {
+        Self::with_options(&AiOptions::new())
+    }
+
+    pub fn with_options(options: &AiOptions) -> MonteCarloAI {
         MonteCarloAI {
-            rng: ChaCha8Rng::from_rng(rand::thread_rng()).unwrap(),
-            ai: WrappedMonteCarloAI::new(Duration::new(1, 0)),
+            rng: options.rng(),
+            ai: options.mcts(),
         }
     }
 
-    pub fn choose(&mut self, g: &Game) -> Pos {
-        self.ai.choose(&mut self.rng, g).into()
+    /// Wrapper around [AI::choose] to return JsError.
+    pub fn choose(&mut self, g: &Game) -> Result<Pos, JsError> {
+        Ok(self.ai.choose(&mut self.rng, g)?.into())
+    }
+
+    /// Wrapper around [Ponder::ponder], to search while waiting for the next
+    /// move. Call with a small number of playouts, so the page stays responsive.
+    pub fn ponder(&mut self, g: &Game, playouts: usize) -> usize {
+        self.ai.ponder(&mut self.rng, g, playouts)
     }
 
     pub fn runs(&self) -> usize {
@@ -78,11 +212,224 @@ impl MonteCarloAI {
         self.ai.last_results.board[board_pos][square_pos]
     }
 
-    pub fn best(&self) -> Pos{
-        self.ai.last_results.best().into()
+    /// Returns the All-Moves-As-First results for the move, which are only
+    /// collected when RAVE is enabled.
+    pub fn rave_stats(&self, board_pos: usize, square_pos: usize) -> Stats {
+        self.ai.last_rave.board[board_pos][square_pos]
+    }
+
+    /// Returns the best move from the last search, or None if nothing has
+    /// been searched yet.
+    pub fn best(&self) -> Option<Pos> {
+        self.ai.last_results.best().map(Pos::from)
     }
 
     pub fn totals(&self) -> Stats {
         self.ai.last_results.totals()
     }
 }
+
+impl Default for MonteCarloAI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The most nodes a [Search] keeps in its tree, so a long analysis doesn't
+/// run out of memory.
+const MAX_SEARCH_NODES: usize = 1_000_000;
+
+/// A Monte Carlo search that runs a little at a time, so it can be spread
+/// across animation frames without blocking the page. The search tree is kept
+/// when moving on to the next position.
+#[wasm_bindgen]
+pub struct Search {
+    rng: ChaCha8Rng,
+    ai: MctsAI<Policy>,
+    game: Game,
+
+    /// The most time and playouts for each position, counted from when it
+    /// was set.
+    time: Duration,
+    max_playouts: usize,
+    start: Instant,
+
+    /// The playouts kept from earlier searches when the position was set,
+    /// which don't count towards its budget.
+    initial_runs: usize,
+}
+
+#[wasm_bindgen]
+impl Search {
+    #[wasm_bindgen(constructor)]
+    pub fn new(g: &Game) -> Search {
+        Self::with_options(g, &AiOptions::new())
+    }
+
+    /// Creates the search, which stops once it has used up the budget in the
+    /// options for the position, or the tree reaches [MAX_SEARCH_NODES].
+    pub fn with_options(g: &Game, options: &AiOptions) -> Search {
+        let mut search = Search {
+            rng: options.rng(),
+            ai: options.mcts(),
+            game: *g,
+            time: Duration::from_secs_f64(options.time_ms.max(0.0) / 1000.0),
+            max_playouts: match options.playouts {
+                0 => usize::MAX,
+                playouts => playouts,
+            },
+            start: Instant::now(),
+            initial_runs: 0,
+        };
+        search.set_game(g);
+        search
+    }
+
+    /// Moves the search on to the position, keeping the results for it if it
+    /// follows on from the current position.
+    pub fn set_game(&mut self, g: &Game) {
+        self.game = *g;
+        self.start = Instant::now();
+        self.ai.ponder(&mut self.rng, g, 0);
+        self.initial_runs = self.ai.last_results.runs;
+    }
+
+    /// Returns true once there's nothing more to search, as the game is over or
+    /// the budget is used up.
+    pub fn done(&self) -> bool {
+        !self.game.playable()
+            || self.start.elapsed() > self.time
+            || self.playouts_left() == 0
+            || self.ai.tree_size() >= MAX_SEARCH_NODES
+    }
+
+    /// Searches for at most `max_playouts` more playouts, returning the number
+    /// done.
+    pub fn step(&mut self, max_playouts: usize) -> usize {
+        let mut playouts = 0;
+        while playouts < max_playouts && !self.done() {
+            let step = PONDER_STEP
+                .min(max_playouts - playouts)
+                .min(self.playouts_left());
+            playouts += self.ai.ponder(&mut self.rng, &self.game, step);
+        }
+        playouts
+    }
+
+    /// Searches for about `ms` milliseconds, returning the number of playouts
+    /// done.
+    pub fn step_for(&mut self, ms: f64) -> usize {
+        let time = Duration::from_secs_f64(ms.max(0.0) / 1000.0);
+        let start = Instant::now();
+        let mut playouts = 0;
+        while start.elapsed() <= time && !self.done() {
+            let step = PONDER_STEP.min(self.playouts_left());
+            playouts += self.ai.ponder(&mut self.rng, &self.game, step);
+        }
+        playouts
+    }
+
+    /// Returns the move that would be picked if the search stopped now.
+    pub fn current_best(&self) -> Option<Pos> {
+        if !self.game.playable() {
+            return None;
+        }
+        self.ai.best().map(Pos::from)
+    }
+
+    pub fn current_stats(&self, board_pos: usize, square_pos: usize) -> Stats {
+        self.ai.last_results.board[board_pos][square_pos]
+    }
+
+    pub fn runs(&self) -> usize {
+        self.ai.last_results.runs
+    }
+
+    pub fn totals(&self) -> Stats {
+        self.ai.last_results.totals()
+    }
+}
+
+impl Search {
+    /// Returns the number of playouts left in the budget for the position.
+    fn playouts_left(&self) -> usize {
+        let done = self.ai.last_results.runs - self.initial_runs;
+        self.max_playouts.saturating_sub(done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::ai_wasm::*;
+
+    #[test]
+    fn options() {
+        let options = AiOptions::new();
+        assert_eq!(options.budget(), Budget::Time(Duration::from_secs(1)));
+
+        let options = AiOptions::with_difficulty(Difficulty::Easy);
+        assert_eq!(
+            options.budget(),
+            Budget::First {
+                time: Duration::from_secs(1),
+                playouts: 100,
+                nodes: usize::MAX,
+            }
+        );
+        assert_eq!(
+            AiOptions::with_difficulty(Difficulty::Expert).policy,
+            Policy::Greedy
+        );
+    }
+
+    #[test]
+    fn seed() {
+        let options = AiOptions {
+            seed: Some(42),
+            ..AiOptions::with_difficulty(Difficulty::Easy)
+        };
+
+        // The same seed plays the same game.
+        let play = || {
+            let mut ai = MonteCarloAI::with_options(&options);
+            let mut g = Game::default();
+            for _ in 0..5 {
+                let p = ai.choose(&g).unwrap();
+                g.play(p.board_pos, p.square_pos).unwrap();
+            }
+            g.to_notation()
+        };
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn search_budget() {
+        let options = AiOptions {
+            time_ms: 60_000.0,
+            playouts: 250,
+            seed: Some(0),
+            ..AiOptions::new()
+        };
+
+        // The search stops once the budget is used up, even part way through a step.
+        let mut g = Game::default();
+        let mut search = Search::with_options(&g, &options);
+        assert!(!search.done());
+        assert_eq!(search.step(10_000), 250);
+        assert!(search.done());
+        assert_eq!(search.step(10_000), 0);
+        assert_eq!(search.step_for(10.0), 0);
+
+        // And starts again on the next position, not counting the playouts
+        // kept from searching the one before.
+        g.play(4, 4).unwrap();
+        search.set_game(&g);
+        let kept = search.runs();
+        assert!(kept > 0);
+        assert!(!search.done());
+        assert_eq!(search.step(50), 50);
+        assert_eq!(search.step_for(60_000.0), 200);
+        assert_eq!(search.runs(), kept + 250);
+    }
+}
@@ -0,0 +1,598 @@
+//! A deterministic negamax search with alpha-beta pruning.
+use crate::ai::Budget;
+use crate::ai::Move;
+use crate::ai::Score;
+use crate::ai::SearchInfo;
+use crate::ai::AI;
+use crate::bitboard;
+use crate::error::AiError;
+use crate::rules::DrawnBoards;
+use crate::solver::Value;
+use crate::transposition;
+use crate::transposition::Bound;
+use crate::transposition::SearchEntry;
+use crate::transposition::TableStats;
+use crate::transposition::TranspositionTable;
+use crate::Game;
+use crate::PossibleMoves;
+use crate::Square;
+use crate::Winner;
+use core::time::Duration;
+use instant::Instant; // Works on wasm instead of use std::time::Instant;
+use rand::RngCore;
+
+/// The score of a won game. Wins found sooner score higher, so all won
+/// positions score above `WIN - MAX_DEPTH`.
+pub const WIN: i32 = 1_000_000;
+
+/// The deepest search possible, as a game lasts at most 81 moves.
+pub const MAX_DEPTH: usize = 81;
+
+/// Scores positions that aren't over, for the search to compare.
+pub trait Evaluator {
+    /// Returns the score of the position for the player to move, with higher
+    /// being better. The score should be well within ±[WIN].
+    fn evaluate(&self, game: &Game) -> i32;
+}
+
+/// The default evaluation, which adds up the features of the position that
+/// are good for the player to move, and takes away those good for the opponent.
+#[derive(Debug, Clone, Copy)]
+pub struct Heuristic {
+    /// For each board won.
+    pub board_win: i32,
+
+    /// For each line on an open board that needs one more square.
+    pub board_threat: i32,
+
+    /// For each line of boards that needs one more open board.
+    pub mega_threat: i32,
+
+    /// For being able to play on any board, having been sent to a finished one.
+    pub freedom: i32,
+}
+
+impl Default for Heuristic {
+    fn default() -> Self {
+        Heuristic {
+            board_win: 100,
+            board_threat: 10,
+            mega_threat: 300,
+            freedom: 30,
+        }
+    }
+}
+
+impl Heuristic {
+    /// Returns the score of the player's pieces, ignoring the freedom to move.
+    fn score(&self, game: &Game, player: Square, opponent: Square) -> i32 {
+        let mega = game.megaboard();
+        let open = mega.open();
+
+        let mut score = 0;
+        for board_pos in bitboard::positions(open) {
+            let board = &game[board_pos];
+            score += self.board_threat
+                * bitboard::threats(board.mask(player), board.mask(opponent)) as i32;
+        }
+
+        let drawn = mega.mask(Square::None);
+        let (mine, blocked) = match mega.rules().drawn_boards {
+            DrawnBoards::Neither => (mega.mask(player), mega.mask(opponent) | drawn),
+            DrawnBoards::Both => (mega.mask(player) | drawn, mega.mask(opponent)),
+        };
+
+        score += self.board_win * mega.mask(player).count_ones() as i32;
+        score += self.mega_threat * bitboard::threats(mine, blocked) as i32;
+
+        score
+    }
+}
+
+impl Evaluator for Heuristic {
+    fn evaluate(&self, game: &Game) -> i32 {
+        let me = game.current_player();
+        let opponent = me.opponent();
+
+        let mut score = self.score(game, me, opponent) - self.score(game, opponent, me);
+        if game.current_board().is_none() {
+            score += self.freedom;
+        }
+
+        score
+    }
+}
+
+/// The outcome of the last search.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub struct SearchResult {
+    /// The move picked.
+    pub best: (usize, usize),
+
+    /// The score of the move for the player who searched.
+    pub score: i32,
+
+    /// The deepest search that completed.
+    pub depth: usize,
+
+    /// The number of positions searched.
+    pub nodes: usize,
+
+    /// How the transposition table was used, if there is one.
+    pub table: Option<TableStats>,
+}
+
+/// Picks the next move with a negamax search using alpha-beta pruning.
+///
+/// The search deepens one move at a time until the budget or maximum depth is
+/// reached, and plays the best move from the deepest search that finished.
+/// Each search tries the previous best move first, and then moves that win a
+/// board, so more of the tree can be pruned. Results are kept in a
+/// transposition table, so positions reached by different move orders, or
+/// searched on earlier turns, aren't searched again.
+pub struct AlphaBetaAI<E: Evaluator = Heuristic> {
+    /// Positions evaluated count as playouts.
+    budget: Budget,
+    max_depth: usize,
+    evaluator: E,
+    table: Option<TranspositionTable<SearchEntry>>,
+
+    pub last_results: SearchResult,
+
+    // State for the current search.
+    start: Instant,
+    nodes: usize,
+    evaluations: usize,
+    aborted: bool,
+}
+
+impl AlphaBetaAI<Heuristic> {
+    pub fn new(time_limit: Duration) -> Self {
+        Self::with_budget(Budget::Time(time_limit))
+    }
+
+    pub fn with_budget(budget: Budget) -> Self {
+        Self::with_evaluator(budget, Heuristic::default())
+    }
+}
+
+impl<E: Evaluator> AlphaBetaAI<E> {
+    pub fn with_evaluator(budget: Budget, evaluator: E) -> Self {
+        AlphaBetaAI {
+            budget,
+            max_depth: MAX_DEPTH,
+            evaluator,
+            table: Some(TranspositionTable::new(transposition::DEFAULT_SIZE)),
+            last_results: SearchResult::default(),
+            start: Instant::now(),
+            nodes: 0,
+            evaluations: 0,
+            aborted: false,
+        }
+    }
+
+    /// Limits how many moves ahead to search.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        assert!(max_depth > 0, "the search must be at least one move deep");
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the size of the transposition table in bytes, with 0 for no table.
+    pub fn with_table_size(mut self, size: usize) -> Self {
+        self.table = if size == 0 {
+            None
+        } else {
+            Some(TranspositionTable::new(size))
+        };
+        self
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Returns the score of the position for the player to move, or None if
+    /// the budget ran out.
+    fn negamax(
+        &mut self,
+        g: &mut Game,
+        depth: usize,
+        mut alpha: i32,
+        mut beta: i32,
+        ply: usize,
+    ) -> Option<i32> {
+        if !g.playable() {
+            let winner = g.winner();
+            return Some(if winner == Square::None {
+                0
+            } else if winner == g.current_player() {
+                WIN - ply as i32
+            } else {
+                -(WIN - ply as i32)
+            });
+        }
+
+        if depth == 0 {
+            self.evaluations += 1;
+            return Some(self.evaluator.evaluate(g));
+        }
+
+        self.nodes += 1;
+        if self.aborted
+            || self
+                .budget
+                .exhausted(self.start, self.evaluations, self.nodes + self.evaluations)
+        {
+            self.aborted = true;
+            return None;
+        }
+
+        let original_alpha = alpha;
+        let mut first = None;
+        if let Some(table) = &mut self.table {
+            if let Some((stored, entry)) = table.probe(g.hash()) {
+                first = entry.best.map(|(b, s)| (b as usize, s as usize));
+
+                if stored as usize >= depth {
+                    let score = from_table(entry.score, ply);
+                    match entry.bound {
+                        Bound::Exact => return Some(score),
+                        Bound::Lower => alpha = alpha.max(score),
+                        Bound::Upper => beta = beta.min(score),
+                    }
+                    if alpha >= beta {
+                        return Some(score);
+                    }
+                }
+            }
+        }
+
+        let mut best = i32::MIN + 1;
+        let mut best_move = None;
+        for (board_pos, square_pos) in ordered_moves(g, first) {
+            g.play(board_pos, square_pos).expect("legal move");
+            let score = self.negamax(g, depth - 1, -beta, -alpha, ply + 1);
+            g.undo().expect("undo legal move");
+
+            let score = -score?;
+            if score > best {
+                best = score;
+                best_move = Some((board_pos as u8, square_pos as u8));
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if let Some(table) = &mut self.table {
+            let bound = if best <= original_alpha {
+                Bound::Upper
+            } else if best >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+
+            let entry = SearchEntry {
+                bound,
+                score: to_table(best, ply),
+                best: best_move,
+            };
+            table.store(g.hash(), depth as u8, entry);
+        }
+
+        Some(best)
+    }
+
+    /// Returns the best move followed by the best replies stored in the table,
+    /// up to the depth searched.
+    fn pv(&mut self, game: &Game, best: (usize, usize), depth: usize) -> Vec<(usize, usize)> {
+        let mut g = *game;
+        let mut pv = vec![best];
+        g.play(best.0, best.1).expect("legal move");
+
+        while pv.len() < depth && g.playable() {
+            let entry = self
+                .table
+                .as_mut()
+                .and_then(|table| table.probe(g.hash()))
+                .and_then(|(_, entry)| entry.best);
+
+            let (board_pos, square_pos) = match entry {
+                Some((b, s)) if g.is_legal(b as usize, s as usize) => (b as usize, s as usize),
+                _ => break,
+            };
+            g.play(board_pos, square_pos).expect("legal move");
+            pv.push((board_pos, square_pos));
+        }
+
+        pv
+    }
+
+    /// Searches every move at the root to the depth, returning the best move
+    /// and its score, or None if the budget ran out.
+    fn search_root(
+        &mut self,
+        game: &Game,
+        depth: usize,
+        first: Option<(usize, usize)>,
+    ) -> Option<((usize, usize), i32)> {
+        let mut g = *game;
+        let mut alpha = -WIN - 1;
+        let mut best = None;
+
+        for m in ordered_moves(game, first) {
+            g.play(m.0, m.1).expect("legal move");
+            let score = self.negamax(&mut g, depth - 1, -WIN - 1, -alpha, 1);
+            g.undo().expect("undo legal move");
+
+            let score = -score?;
+            if best.is_none() || score > alpha {
+                alpha = score;
+                best = Some((m, score));
+            }
+        }
+
+        best
+    }
+}
+
+/// Returns the legal moves, most promising first.
+pub(crate) fn ordered_moves(g: &Game, first: Option<(usize, usize)>) -> Vec<(usize, usize)> {
+    let me = g.current_player();
+    let open = g.megaboard().open();
+
+    let mut moves: Vec<((usize, usize), i32)> = g
+        .legal_moves()
+        .map(|(board_pos, square_pos)| {
+            let m = (board_pos, square_pos);
+            let board = &g[board_pos];
+
+            let mut priority = 0;
+            if Some(m) == first {
+                priority += 10;
+            }
+            if board.winner() == Square::None && bitboard::is_win(board.mask(me) | 1 << square_pos)
+            {
+                priority += 2;
+            }
+            if open & (1 << square_pos) == 0 {
+                // Lets the opponent play anywhere.
+                priority -= 1;
+            }
+
+            (m, priority)
+        })
+        .collect();
+
+    // Stable, so equal moves stay in board then square order.
+    moves.sort_by_key(|&(_, priority)| -priority);
+    moves.into_iter().map(|(m, _)| m).collect()
+}
+
+/// Returns the score to store in the table. Wins are stored as the distance
+/// from this position, rather than from the root, as the position could be
+/// reached at a different distance from the root in a later search.
+fn to_table(score: i32, ply: usize) -> i32 {
+    if score > WIN - MAX_DEPTH as i32 {
+        score + ply as i32
+    } else if score < -(WIN - MAX_DEPTH as i32) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Returns the score from the table, as the distance from the root.
+fn from_table(score: i32, ply: usize) -> i32 {
+    if score > WIN - MAX_DEPTH as i32 {
+        score - ply as i32
+    } else if score < -(WIN - MAX_DEPTH as i32) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+impl<E: Evaluator> AI for AlphaBetaAI<E> {
+    fn choose<R: RngCore>(&mut self, _r: R, game: &Game) -> Result<Move, AiError> {
+        if !game.playable() {
+            return Err(AiError::NoLegalMoves);
+        }
+
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.evaluations = 0;
+        self.aborted = false;
+        if let Some(table) = &mut self.table {
+            table.new_search();
+        }
+
+        let remaining = MAX_DEPTH - game.turns();
+        let mut result = SearchResult {
+            best: game.legal_moves().next().unwrap(),
+            ..Default::default()
+        };
+
+        for depth in 1..=self.max_depth.min(remaining) {
+            let first = if depth == 1 { None } else { Some(result.best) };
+            let (best, score) = match self.search_root(game, depth, first) {
+                Some(found) => found,
+                None => break, // Out of budget, so use the last complete search.
+            };
+
+            result.best = best;
+            result.score = score;
+            result.depth = depth;
+
+            // Nothing will change once the result is known.
+            if score.abs() > WIN - MAX_DEPTH as i32 {
+                break;
+            }
+        }
+
+        result.nodes = self.nodes + self.evaluations;
+        result.table = self.table.as_ref().map(|table| table.stats());
+        self.last_results = result;
+
+        let score = if result.score > WIN - MAX_DEPTH as i32 {
+            Score::Solved(Value::Win)
+        } else if result.score < -(WIN - MAX_DEPTH as i32) {
+            Score::Solved(Value::Loss)
+        } else {
+            Score::Evaluation(result.score)
+        };
+
+        let (board_pos, square_pos) = result.best;
+        Ok(Move::new(board_pos, square_pos).with_info(SearchInfo {
+            score,
+            pv: self.pv(game, result.best, result.depth),
+            nodes: result.nodes,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::ai::AI;
+    use crate::alphabeta::*;
+    use crate::Game;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Returns the move picked by the AI.
+    fn choose(game: &Game, depth: usize) -> ((usize, usize), SearchResult) {
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = AlphaBetaAI::with_budget(Budget::Nodes(1_000_000)).with_max_depth(depth);
+        let m = ai.choose(rng, game).unwrap();
+        (m.pos(), ai.last_results)
+    }
+
+    #[test]
+    fn winning_move() {
+        // O has won boards 0 and 1, and can win board 2 and the game at square 2.
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO.XX..../..X....../X......../........./........./........./......... O 2 16",
+        )
+        .unwrap();
+
+        let (m, result) = choose(&g, 3);
+        assert_eq!(m, (2, 2));
+        assert_eq!(result.score, WIN - 1);
+        assert_eq!(result.depth, 1);
+
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = AlphaBetaAI::with_budget(Budget::Nodes(1_000_000));
+        let info = ai.choose(rng, &g).unwrap().info.unwrap();
+        assert_eq!(info.score, Score::Solved(Value::Win));
+        assert_eq!(info.pv, vec![(2, 2)]);
+    }
+
+    #[test]
+    fn matches_minimax() {
+        // Alpha-beta pruning doesn't change the score of the best move.
+        let g = Game::from_notation(
+            ".XXXOOOOX/OX.O.OO.X/O.OX...OO/XX.O.OOX./OOX....../X...X.O.X/XOXX...XX/..XX.XXOO/OX.OXO.O. O 3 50",
+        )
+        .unwrap();
+
+        let h = Heuristic::default();
+        fn minimax(g: &mut Game, depth: usize, ply: usize, h: &Heuristic) -> i32 {
+            if !g.playable() {
+                return match g.winner() {
+                    Square::None => 0,
+                    w if w == g.current_player() => WIN - ply as i32,
+                    _ => -(WIN - ply as i32),
+                };
+            }
+            if depth == 0 {
+                return h.evaluate(g);
+            }
+            g.legal_moves()
+                .map(|(b, s)| {
+                    g.play(b, s).unwrap();
+                    let score = -minimax(g, depth - 1, ply + 1, h);
+                    g.undo().unwrap();
+                    score
+                })
+                .max()
+                .unwrap()
+        }
+
+        for depth in 1..=4 {
+            let (_, result) = choose(&g, depth);
+            let mut copy = g;
+            assert_eq!(
+                result.score,
+                minimax(&mut copy, depth, 0, &h),
+                "depth {}",
+                depth
+            );
+        }
+    }
+
+    #[test]
+    fn transpositions() {
+        let g = Game::from_notation(
+            "..OX.O.XO/..X..O.O./..OXO.OXO/OOXOX.XOO/O.OX.X.X./XXX...OXO/..XXXO.O./X.OOOO.X./.XXX.XXO. O - 50",
+        )
+        .unwrap();
+
+        let search = |size| {
+            let rng = ChaCha8Rng::seed_from_u64(0);
+            let mut ai = AlphaBetaAI::with_budget(Budget::Nodes(1_000_000))
+                .with_max_depth(5)
+                .with_table_size(size);
+            ai.choose(rng, &g).unwrap();
+            ai.last_results
+        };
+
+        let without = search(0);
+        let with = search(1024 * 1024);
+        assert_eq!(without.table, None);
+
+        // The table saves work without changing the result.
+        assert_eq!(with.score, without.score);
+        assert!(with.nodes < without.nodes);
+
+        let stats = with.table.unwrap();
+        assert!(stats.hits > 0);
+        assert!(stats.hit_rate() > 0.0);
+    }
+
+    #[test]
+    fn deterministic() {
+        let g = Game::default();
+        assert_eq!(choose(&g, 3), choose(&g, 3));
+
+        let (m, result) = choose(&g, 2);
+        assert_eq!(result.depth, 2);
+        assert!(g.is_legal(m.0, m.1));
+
+        // The principal variation is a legal line, as deep as the search.
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = AlphaBetaAI::with_budget(Budget::Nodes(1_000_000)).with_max_depth(3);
+        let info = ai.choose(rng, &g).unwrap().info.unwrap();
+        assert!(!info.pv.is_empty() && info.pv.len() <= 3);
+        let mut line = g;
+        for (board_pos, square_pos) in info.pv {
+            line.play(board_pos, square_pos).unwrap();
+        }
+
+        // A small budget still picks a legal move.
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = AlphaBetaAI::with_budget(Budget::Nodes(10));
+        let m = ai.choose(rng, &g).unwrap().pos();
+        assert!(g.is_legal(m.0, m.1));
+        assert!(ai.last_results.depth < 2);
+    }
+}
@@ -0,0 +1,148 @@
+//! Helpers for working with 9-bit masks, where bit `n` represents position `n`
+//! in a 3x3 grid:
+//!
+//! ```text
+//! 0 │ 1 │ 2
+//! ──┼───┼──
+//! 3 │ 4 │ 5
+//! ──┼───┼──
+//! 6 │ 7 │ 8
+//! ```
+//!
+//! The same layout is used for the squares within a [crate::Board], and for the
+//! boards within a [crate::MegaBoard].
+use rand::Rng;
+use rand::RngCore;
+
+/// Mask with all nine positions set.
+pub const FULL: u16 = 0b111_111_111;
+
+/// The eight lines that win a grid.
+pub const LINES: [u16; 8] = [
+    // Rows
+    0b000_000_111,
+    0b000_111_000,
+    0b111_000_000,
+    // Cols
+    0b001_001_001,
+    0b010_010_010,
+    0b100_100_100,
+    // Diagonals
+    0b100_010_001,
+    0b001_010_100,
+];
+
+/// Lookup table, indexed by mask, of which masks contain a winning line.
+const WINNING: [bool; 512] = winning_table();
+
+const fn winning_table() -> [bool; 512] {
+    let mut table = [false; 512];
+
+    let mut mask = 0;
+    while mask < table.len() {
+        let mut i = 0;
+        while i < LINES.len() {
+            let line = LINES[i] as usize;
+            if mask & line == line {
+                table[mask] = true;
+            }
+            i += 1;
+        }
+        mask += 1;
+    }
+
+    table
+}
+
+/// Returns the number of lines with two positions in the mask, and none in
+/// `blocked`, so one more position would complete the line.
+#[inline]
+pub fn threats(mask: u16, blocked: u16) -> u32 {
+    LINES
+        .iter()
+        .filter(|&&line| (mask & line).count_ones() == 2 && blocked & line == 0)
+        .count() as u32
+}
+
+/// Returns the positions in `empty` that would complete a line with the mask.
+#[inline]
+pub fn completing(mask: u16, empty: u16) -> u16 {
+    LINES
+        .iter()
+        .filter(|&&line| (mask & line).count_ones() == 2)
+        .fold(0, |squares, &line| squares | (line & !mask))
+        & empty
+}
+
+/// Returns true if the mask contains a complete line.
+#[inline]
+pub fn is_win(mask: u16) -> bool {
+    WINNING[(mask & FULL) as usize]
+}
+
+/// Returns the position of the nth set bit in the mask.
+#[inline]
+pub fn nth(mut mask: u16, n: u32) -> usize {
+    for _ in 0..n {
+        mask &= mask - 1; // Clear the lowest set bit
+    }
+    mask.trailing_zeros() as usize
+}
+
+/// Returns the positions set in the mask, lowest first.
+pub fn positions(mut mask: u16) -> impl Iterator<Item = usize> {
+    core::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let pos = mask.trailing_zeros() as usize;
+        mask &= mask - 1;
+        Some(pos)
+    })
+}
+
+/// Choose a random position set in the mask, or return a None.
+#[inline]
+pub fn choose<R: RngCore>(mask: u16, rng: &mut R) -> Option<usize> {
+    match mask.count_ones() {
+        0 => None,
+        n => Some(nth(mask, rng.gen_range(0..n))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitboard::*;
+
+    #[test]
+    fn winning() {
+        for line in LINES {
+            assert!(is_win(line));
+        }
+
+        assert!(!is_win(0));
+        assert!(!is_win(0b010_010_101)); // X's squares in a drawn grid
+        assert!(is_win(FULL));
+    }
+
+    #[test]
+    fn bits() {
+        let mask = 0b100_010_010;
+        assert_eq!(positions(mask).collect::<Vec<_>>(), vec![1, 4, 8]);
+        assert_eq!(nth(mask, 0), 1);
+        assert_eq!(nth(mask, 2), 8);
+    }
+
+    #[test]
+    fn threats() {
+        // Top left and centre only threaten the diagonal.
+        assert_eq!(super::threats(0b000_010_001, 0), 1);
+        assert_eq!(super::threats(0b000_010_001, 0b100_000_000), 0);
+        assert_eq!(super::threats(0b000_000_011, 0), 1);
+        assert_eq!(super::threats(0b000_011_011, 0), 5);
+
+        assert_eq!(completing(0b000_000_011, FULL), 0b000_000_100);
+        assert_eq!(completing(0b000_000_011, 0), 0);
+        assert_eq!(completing(0b000_010_001, FULL & !0b100_000_000), 0);
+    }
+}
@@ -1,112 +1,174 @@
+use crate::bitboard;
 use crate::error::MyError;
 use crate::error::MyError::*;
+use crate::symmetry::Symmetry;
 use crate::PossibleMoves;
 use crate::Square;
 use crate::Winner;
 use core::fmt::Display;
 use core::fmt::Formatter;
 use core::ops::Index;
-use rand::prelude::SliceRandom;
 
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Board {
-    #[wasm_bindgen(skip)]
-    pub grid: [Square; 9], // TODO rename square
+    /// Bitmask of the squares played by O.
+    o: u16,
 
-    /// Keep track of possible moves
-    moves: Vec<usize>,
+    /// Bitmask of the squares played by X.
+    x: u16,
 
     /// Cache if there is a winner
     winner: Square,
 }
 
-impl Default for Board {
-    fn default() -> Self {
-        use Square::None;
-
-        Board {
-            grid: [
-                None, None, None, //
-                None, None, None, //
-                None, None, None, //
-            ],
-
-            moves: vec![0, 1, 2, 3, 4, 5, 6, 7, 8],
-
-            winner: None,
-        }
-    }
-}
-
 impl From<[Square; 9]> for Board {
     fn from(grid: [Square; 9]) -> Self {
-        Board {
-            grid,
-
-            moves: vec![0, 1, 2, 3, 4, 5, 6, 7, 8],
-
-            winner: Square::None,
+        let mut b = Board::default();
+        for (pos, square) in grid.iter().enumerate() {
+            match square {
+                Square::O => b.o |= 1 << pos,
+                Square::X => b.x |= 1 << pos,
+                Square::None => {}
+            }
         }
+        b.winner = b.check_winner();
+        b
     }
 }
 
 impl Board {
     /// Plays at the specific position. Returns true if this board is over (won or drawn).
     pub fn play(&mut self, pos: usize, player: Square) -> Result<bool, MyError> {
-        if pos >= self.grid.len() {
+        if pos >= 9 {
             return Err(InvalidSquare);
         }
-        if self.grid[pos] != Square::None {
+        if self.empty() & (1 << pos) == 0 {
             return Err(AlreadyPlayed);
         }
         if self.has_winner() {
             return Err(AlreadyWon);
         }
 
-        // Play
-        self.grid[pos] = player;
-        self.winner = self.check_winner();
+        self.place(pos, player);
+
+        Ok(self.winner != Square::None || self.empty() == 0)
+    }
+
+    /// Plays at the specific position, even if this board has already been
+    /// won. Returns true if this board is full.
+    pub fn play_any(&mut self, pos: usize, player: Square) -> Result<bool, MyError> {
+        if pos >= 9 {
+            return Err(InvalidSquare);
+        }
+        if self.empty() & (1 << pos) == 0 {
+            return Err(AlreadyPlayed);
+        }
 
-        // Remove this possible move
-        let x = self.moves.iter().position(|&x| x == pos).unwrap();
-        self.moves.swap_remove(x);
+        self.place(pos, player);
 
-        Ok(self.winner != Square::None || self.moves.is_empty())
+        Ok(self.empty() == 0)
     }
 
-    /*
-        pub fn undo(&mut self, pos: usize) {
-            assert_ne!(self.grid[pos], Square::None);
+    fn place(&mut self, pos: usize, player: Square) {
+        let bit = 1 << pos;
+        match player {
+            Square::O => self.o |= bit,
+            Square::X => self.x |= bit,
+            Square::None => panic!("Square::None can not play"),
+        }
+
+        // The first player to complete a line keeps the board.
+        if self.winner == Square::None {
+            self.winner = self.check_winner();
+        }
+    }
 
-            self.grid[pos] = Square::None;
+    /// Removes the play at the specific position, returning the player who had played there.
+    pub fn undo(&mut self, pos: usize) -> Result<Square, MyError> {
+        if pos >= 9 {
+            return Err(InvalidSquare);
+        }
 
-            // We should have no winner now. We can skip the check_winner
-            assert_eq!(self.check_winner(), Square::None);
-            self.winner = Square::None;
+        let bit = 1 << pos;
+        let player = self[pos];
+        match player {
+            Square::O => self.o &= !bit,
+            Square::X => self.x &= !bit,
+            Square::None => return Err(NotPlayed),
+        }
 
-            self.moves.push(pos);
+        // Keep the winner, unless this removed their line.
+        if self.winner != Square::None && !bitboard::is_win(self.mask(self.winner)) {
+            self.winner = self.check_winner();
         }
-    */
+
+        Ok(player)
+    }
+
+    /// Returns the board with the winner set. Only needed when both players
+    /// have a line, which can happen if won boards can still be played on, as
+    /// the winner was whoever completed theirs first.
+    pub(crate) fn with_winner(mut self, winner: Square) -> Board {
+        self.winner = winner;
+        self
+    }
+
+    /// Returns this board with the symmetry applied.
+    pub fn transform(&self, symmetry: Symmetry) -> Board {
+        Board {
+            o: symmetry.apply_mask(self.o),
+            x: symmetry.apply_mask(self.x),
+            winner: self.winner,
+        }
+    }
+
+    /// Returns a copy of all the squares on this board.
+    ///
+    /// This replaces the public `grid` field, which was removed when the board
+    /// moved to bitboards, as it could be changed without updating them. Use
+    /// `board[pos]` to read a single square.
+    pub fn grid(&self) -> [Square; 9] {
+        let mut grid = [Square::None; 9];
+        for (pos, square) in grid.iter_mut().enumerate() {
+            *square = self[pos];
+        }
+        grid
+    }
+
+    /// Returns the bitmask of squares that have not been played.
+    pub fn empty(&self) -> u16 {
+        !(self.o | self.x) & bitboard::FULL
+    }
+
+    /// Returns the bitmask of squares played by the player.
+    pub fn mask(&self, player: Square) -> u16 {
+        match player {
+            Square::O => self.o,
+            Square::X => self.x,
+            Square::None => self.empty(),
+        }
+    }
 }
 
 impl Board {
     pub fn square(&self, square_pos: usize) -> Result<Square, MyError> {
-        if square_pos >= self.grid.len() {
+        if square_pos >= self.len() {
             return Err(InvalidSquare);
         }
 
-        Ok(self.grid[square_pos])
+        Ok(self[square_pos])
     }
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Board {
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
-        self.grid.len()
+        9
     }
 }
 
@@ -137,11 +199,11 @@ impl Board {
 
 impl PossibleMoves for Board {
     fn choose<R: rand::RngCore>(&self, rng: &mut R) -> Option<usize> {
-        self.moves.choose(rng).copied()
+        bitboard::choose(self.empty(), rng)
     }
 
     fn playable(&self) -> bool {
-        !self.moves.is_empty() && !self.has_winner()
+        self.empty() != 0 && !self.has_winner()
     }
 }
 
@@ -149,7 +211,7 @@ impl Index<(usize, usize)> for Board {
     type Output = Square;
 
     fn index(&self, p: (usize, usize)) -> &Self::Output {
-        self.grid.index(p.1 * 3 + p.0)
+        self.index(p.1 * 3 + p.0)
     }
 }
 
@@ -157,35 +219,32 @@ impl Index<usize> for Board {
     type Output = Square;
 
     fn index(&self, pos: usize) -> &Self::Output {
-        self.grid.index(pos)
-    }
-}
+        assert!(
+            pos < 9,
+            "index out of bounds: the len is 9 but the index is {}",
+            pos
+        );
 
-macro_rules! check {
-    ($grid:expr, $a:literal, $b:literal, $c:literal) => {
-        if $grid[$a] != Square::None && $grid[$a] == $grid[$b] && $grid[$a] == $grid[$c] {
-            return $grid[$a];
+        let bit = 1 << pos;
+        if self.o & bit != 0 {
+            &Square::O
+        } else if self.x & bit != 0 {
+            &Square::X
+        } else {
+            &Square::None
         }
-    };
+    }
 }
 
 impl Board {
     fn check_winner(&self) -> Square {
-        // Rows
-        check!(self.grid, 0, 1, 2);
-        check!(self.grid, 3, 4, 5);
-        check!(self.grid, 6, 7, 8);
-
-        // Cols
-        check!(self.grid, 0, 3, 6);
-        check!(self.grid, 1, 4, 7);
-        check!(self.grid, 2, 5, 8);
-
-        // Diagonals
-        check!(self.grid, 0, 4, 8);
-        check!(self.grid, 2, 4, 6);
-
-        Square::None
+        if bitboard::is_win(self.o) {
+            Square::O
+        } else if bitboard::is_win(self.x) {
+            Square::X
+        } else {
+            Square::None
+        }
     }
 }
 
@@ -232,9 +291,9 @@ impl Display for Board {
                     writeln!(
                         f,
                         "│ {} │ {} │ {} │",
-                        self.grid[3 * row + 0],
-                        self.grid[3 * row + 1],
-                        self.grid[3 * row + 2]
+                        self[3 * row],
+                        self[3 * row + 1],
+                        self[3 * row + 2]
                     )?;
                     if row < 2 {
                         writeln!(f, "│───┼───┼───│")?;
@@ -250,6 +309,7 @@ impl Display for Board {
 
 #[cfg(test)]
 mod tests {
+    use crate::error::MyError;
     use crate::Board;
     use crate::PossibleMoves;
     use crate::Square;
@@ -320,16 +380,29 @@ mod tests {
         assert!(!b.playable());
     }
 
+    #[test]
+    fn undo() {
+        let mut b = Board::default();
+        b.play(0, Square::X).unwrap();
+        b.play(4, Square::X).unwrap();
+        b.play(8, Square::X).unwrap();
+        assert_eq!(b.winner(), Square::X);
+
+        assert_eq!(b.undo(8), Ok(Square::X));
+        assert_eq!(b.winner(), Square::None);
+        assert!(b.playable());
+
+        assert_eq!(b.undo(8), Err(MyError::NotPlayed));
+        assert_eq!(b.undo(9), Err(MyError::InvalidSquare));
+    }
+
     #[test]
     fn display() {
-        let b = Board {
-            grid: [
-                O, X, None, //
-                None, O, X, //
-                X, X, None, //
-            ],
-            ..Default::default()
-        };
+        let b = Board::from([
+            O, X, None, //
+            None, O, X, //
+            X, X, None, //
+        ]);
 
         assert_eq!(
             format!("{}", b),
@@ -0,0 +1,357 @@
+//! An opening book, of moves picked ahead of time for the first few positions
+//! of a game, so they don't need to be searched every game.
+//!
+//! Positions are keyed by their [Game::canonical_hash], so each set of
+//! symmetric positions has one entry, with its move stored for the canonical
+//! position and transformed back when looked up.
+//!
+//! Books are built either by searching every position up to a number of moves
+//! with an [AI], or from the results of many games with a [BookBuilder], and
+//! saved in a compact binary format:
+//!
+//! ```text
+//! "UTTB"   magic
+//! u8       version, currently 1
+//! u32      number of entries
+//! entries  sorted by key, each a u64 key followed by a u8 move
+//! ```
+//!
+//! Numbers are little-endian, and moves are stored as `board_pos * 9 + square_pos`.
+use crate::ai::Move;
+use crate::ai::AI;
+use crate::error::AiError;
+use crate::error::BookError;
+use crate::monte_carlo::Stats;
+use crate::Game;
+use crate::PossibleMoves;
+use crate::Square;
+use crate::Winner;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Read;
+use std::io::Write;
+
+const MAGIC: &[u8; 4] = b"UTTB";
+const VERSION: u8 = 1;
+
+/// A move for each position in the book.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Book {
+    /// The move for each canonical position, as `board_pos * 9 + square_pos`.
+    entries: HashMap<u64, u8>,
+}
+
+impl Book {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Sets the move to play in the position, and any symmetric position.
+    pub fn insert(&mut self, game: &Game, board_pos: usize, square_pos: usize) {
+        let (canonical, symmetry) = game.canonical();
+        let m = symmetry.apply(board_pos) * 9 + symmetry.apply(square_pos);
+        self.entries.insert(canonical.hash(), m as u8);
+    }
+
+    /// Returns the (board_pos, square_pos) to play in the position, if it's in
+    /// the book.
+    pub fn lookup(&self, game: &Game) -> Option<(usize, usize)> {
+        let (canonical, symmetry) = game.canonical();
+        let m = *self.entries.get(&canonical.hash())? as usize;
+
+        let inverse = symmetry.inverse();
+        let (board_pos, square_pos) = (inverse.apply(m / 9), inverse.apply(m % 9));
+
+        // Guard against a hash collision with a different position.
+        if !game.is_legal(board_pos, square_pos) {
+            return None;
+        }
+        Some((board_pos, square_pos))
+    }
+
+    /// Creates a book with the move picked by the AI for every position
+    /// reachable from the game with fewer than `plies` moves played in total.
+    /// Symmetric positions are only searched once.
+    pub fn generate<A: AI, R: RngCore>(
+        ai: &mut A,
+        mut r: R,
+        game: &Game,
+        plies: usize,
+    ) -> Result<Book, AiError> {
+        let mut book = Book::new();
+        let mut seen = HashSet::new();
+        let mut positions = vec![*game];
+
+        while let Some(g) = positions.pop() {
+            if g.turns() >= plies || !g.playable() || !seen.insert(g.canonical_hash()) {
+                continue;
+            }
+
+            let m = ai.choose(&mut r, &g)?;
+            book.insert(&g, m.board_pos, m.square_pos);
+
+            for (board_pos, square_pos) in g.distinct_moves() {
+                let mut next = g;
+                next.play(board_pos, square_pos).expect("legal move");
+                positions.push(next);
+            }
+        }
+
+        Ok(book)
+    }
+
+    /// Writes the book in the binary format.
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), BookError> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort();
+
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&(entries.len() as u32).to_le_bytes())?;
+        for (key, m) in entries {
+            w.write_all(&key.to_le_bytes())?;
+            w.write_all(&[*m])?;
+        }
+        Ok(())
+    }
+
+    /// Reads a book in the binary format.
+    pub fn read<R: Read>(mut r: R) -> Result<Book, BookError> {
+        let mut header = [0u8; 9];
+        r.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(BookError::InvalidMagic);
+        }
+        if header[4] != VERSION {
+            return Err(BookError::UnsupportedVersion(header[4]));
+        }
+
+        let len = u32::from_le_bytes(header[5..9].try_into().unwrap());
+        let mut entries = HashMap::new();
+        for _ in 0..len {
+            let mut entry = [0u8; 9];
+            r.read_exact(&mut entry)?;
+
+            let key = u64::from_le_bytes(entry[..8].try_into().unwrap());
+            if entry[8] >= 81 {
+                return Err(BookError::InvalidMove(entry[8]));
+            }
+            entries.insert(key, entry[8]);
+        }
+
+        Ok(Book { entries })
+    }
+}
+
+/// Builds a book from the results of many games, such as self-play, picking
+/// the move with the best results in each position.
+#[derive(Debug, Default)]
+pub struct BookBuilder {
+    plies: usize,
+
+    /// The results of each move in each canonical position, for the player
+    /// making the move.
+    results: HashMap<u64, [Stats; 81]>,
+}
+
+impl BookBuilder {
+    /// Creates a builder that records the first `plies` moves of each game.
+    pub fn new(plies: usize) -> Self {
+        BookBuilder {
+            plies,
+            results: HashMap::new(),
+        }
+    }
+
+    /// Records the result of a finished game against each of its early moves.
+    pub fn add_game(&mut self, game: &Game) {
+        let winner = game.winner();
+
+        let mut g = *game;
+        while let Ok((board_pos, square_pos)) = g.undo() {
+            if g.turns() >= self.plies {
+                continue;
+            }
+
+            let (canonical, symmetry) = g.canonical();
+            let m = symmetry.apply(board_pos) * 9 + symmetry.apply(square_pos);
+            let stat = &mut self
+                .results
+                .entry(canonical.hash())
+                .or_insert([Stats::default(); 81])[m];
+
+            if winner == g.current_player() {
+                stat.wins += 1;
+            } else if winner != Square::None {
+                stat.loses += 1;
+            }
+            stat.totals += 1;
+        }
+    }
+
+    /// Returns a book with the move with the best results in each position,
+    /// only counting moves played in at least `min_games` games.
+    pub fn build(&self, min_games: usize) -> Book {
+        let mut entries = HashMap::new();
+        for (&key, results) in &self.results {
+            let best = results
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.totals > 0 && s.totals >= min_games)
+                .max_by(|(_, a), (_, b)| a.expected().partial_cmp(&b.expected()).unwrap());
+
+            if let Some((m, _)) = best {
+                entries.insert(key, m as u8);
+            }
+        }
+
+        Book { entries }
+    }
+}
+
+/// Plays from the book when the position is in it, otherwise asks the inner AI.
+pub struct BookAI<A: AI> {
+    book: Book,
+    inner: A,
+
+    /// True if the last move came from the book.
+    pub last_from_book: bool,
+}
+
+impl<A: AI> BookAI<A> {
+    pub fn new(book: Book, inner: A) -> Self {
+        BookAI {
+            book,
+            inner,
+            last_from_book: false,
+        }
+    }
+
+    pub fn book(&self) -> &Book {
+        &self.book
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+impl<A: AI> AI for BookAI<A> {
+    fn choose<R: RngCore>(&mut self, r: R, game: &Game) -> Result<Move, AiError> {
+        if !game.playable() {
+            return Err(AiError::NoLegalMoves);
+        }
+
+        self.last_from_book = false;
+        if let Some((board_pos, square_pos)) = self.book.lookup(game) {
+            self.last_from_book = true;
+            return Ok(Move::new(board_pos, square_pos));
+        }
+
+        self.inner.choose(r, game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::ai::MonteCarloAI;
+    use crate::ai::RandomAI;
+    use crate::ai::AI;
+    use crate::book::*;
+    use crate::symmetry::Symmetry;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn symmetric_lookup() {
+        let mut g = Game::default();
+        g.play(0, 4).unwrap();
+
+        let mut book = Book::new();
+        book.insert(&g, 4, 0);
+        assert_eq!(book.len(), 1);
+        assert_eq!(book.lookup(&g), Some((4, 0)));
+
+        // The same move, rotated with the position.
+        for s in Symmetry::ALL {
+            let rotated = g.transform(s);
+            let (board_pos, square_pos) = book.lookup(&rotated).unwrap();
+            assert_eq!((board_pos, square_pos), (s.apply(4), s.apply(0)));
+        }
+
+        assert_eq!(book.lookup(&Game::default()), None);
+    }
+
+    #[test]
+    fn generate_and_round_trip() {
+        let rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = MonteCarloAI::with_budget(Budget::Playouts(10));
+        let book = Book::generate(&mut ai, rng, &Game::default(), 2).unwrap();
+
+        // The empty board, and the 15 distinct first moves.
+        assert_eq!(book.len(), 16);
+
+        let mut bytes = Vec::new();
+        book.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 9 + 16 * 9);
+        assert_eq!(Book::read(bytes.as_slice()).unwrap(), book);
+
+        bytes[0] = b'X';
+        assert!(matches!(
+            Book::read(bytes.as_slice()),
+            Err(BookError::InvalidMagic)
+        ));
+        assert!(matches!(Book::read(&bytes[..3]), Err(BookError::Io(_))));
+    }
+
+    #[test]
+    fn builder() {
+        let mut r = ChaCha8Rng::seed_from_u64(0);
+        let mut builder = BookBuilder::new(1);
+        for _ in 0..200 {
+            let mut g = Game::default();
+            while g.playable() {
+                let m = RandomAI::default().choose(&mut r, &g).unwrap();
+                g.play(m.board_pos, m.square_pos).unwrap();
+            }
+            builder.add_game(&g);
+        }
+
+        // Only the empty board is recorded.
+        let book = builder.build(1);
+        assert_eq!(book.len(), 1);
+        let (board_pos, square_pos) = book.lookup(&Game::default()).unwrap();
+        assert!(Game::default().is_legal(board_pos, square_pos));
+
+        assert!(builder.build(1000).is_empty());
+    }
+
+    #[test]
+    fn book_ai() {
+        let mut r = ChaCha8Rng::seed_from_u64(0);
+        let mut book = Book::new();
+        book.insert(&Game::default(), 4, 4);
+
+        let mut ai = BookAI::new(book, RandomAI::default());
+        let m = ai.choose(&mut r, &Game::default()).unwrap();
+        assert_eq!(m.pos(), (4, 4));
+        assert!(ai.last_from_book);
+
+        let mut g = Game::default();
+        g.play(4, 4).unwrap();
+        let m = ai.choose(&mut r, &g).unwrap();
+        assert!(g.is_legal(m.board_pos, m.square_pos));
+        assert!(!ai.last_from_book);
+    }
+}
@@ -28,4 +28,74 @@ pub enum MyError {
 
     #[error("Board has already been won")]
     AlreadyWon,
+
+    #[error("Game is over")]
+    GameOver,
+
+    #[error("Square has not been played")]
+    NotPlayed,
+
+    #[error("No moves to undo")]
+    NothingToUndo,
+
+    #[error("Invalid notation")]
+    InvalidNotation,
+
+    #[error("Number of pieces does not match the player to move")]
+    InvalidPieceCount,
+
+    #[error("Board has been won by both players")]
+    InvalidWinner,
+
+    #[error("Forced board can not be played on")]
+    InvalidForcedBoard,
+
+    #[error("Turns does not match the number of pieces")]
+    InvalidTurns,
+
+    #[error("Invalid rules")]
+    InvalidRules,
+}
+
+/// Errors from an [crate::ai::AI] picking a move.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum AiError {
+    #[error("Game is over, so there are no legal moves")]
+    NoLegalMoves,
+}
+
+/// Errors from reading or writing a [crate::book::Book].
+#[derive(thiserror::Error, Debug)]
+pub enum BookError {
+    #[error("Not an opening book")]
+    InvalidMagic,
+
+    #[error("Unsupported book version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Invalid move {0} in book")]
+    InvalidMove(u8),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Errors from parsing a [crate::record::GameRecord].
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum RecordError {
+    #[error("Invalid header on line {0}")]
+    InvalidHeader(usize),
+
+    #[error("Invalid move \"{0}\"")]
+    InvalidMove(String),
+
+    #[error("Illegal move {index}: {error}")]
+    IllegalMove { index: usize, error: MyError },
+
+    #[error("Result \"{0}\" does not match the game")]
+    WrongResult(String),
+
+    #[error("Invalid rules \"{0}\"")]
+    InvalidRules(String),
 }
@@ -1,5 +1,9 @@
+use crate::bitboard;
 use crate::error::MyError;
 use crate::error::MyError::*;
+use crate::rules::Rules;
+use crate::symmetry::Symmetry;
+use crate::zobrist;
 use crate::Board;
 use crate::MegaBoard;
 use crate::PossibleMoves;
@@ -10,11 +14,36 @@ use core::ops::Index;
 use rand::RngCore;
 use std::fmt::Display;
 
+#[cfg(feature = "wasm")]
+use crate::ai_wasm::Pos;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+/// A move that has been played, with the state needed to undo it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Played {
+    board_pos: u8,
+    square_pos: u8,
+
+    /// The board being played on before this move.
+    current_board: Option<u8>,
+}
+
+/// The maximum number of moves in a game, one per square.
+const MAX_MOVES: usize = 81;
+
+/// The outcome of a game.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Eq, Hash, PartialEq, Copy, Clone)]
+pub enum GameResult {
+    InProgress,
+    OWins,
+    XWins,
+    Draw,
+}
+
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Game {
     board: MegaBoard,
 
@@ -26,6 +55,13 @@ pub struct Game {
 
     /// The current turn.
     turns: usize,
+
+    /// Zobrist hash of the position.
+    hash: u64,
+
+    /// The moves played so far, of which the first `moves` are valid.
+    history: [Played; MAX_MOVES],
+    moves: usize,
 }
 
 impl Default for Game {
@@ -35,6 +71,9 @@ impl Default for Game {
             current_player: Square::O,
             current_board: None,
             turns: 0,
+            hash: 0,
+            history: [Played::default(); MAX_MOVES],
+            moves: 0,
         }
     }
 }
@@ -76,8 +115,41 @@ impl Display for Game {
 }
 
 impl Game {
+    /// Creates a new game played with the rules.
+    pub fn with_rules(rules: Rules) -> Game {
+        Game {
+            board: MegaBoard::with_rules(rules),
+            ..Default::default()
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.board.rules()
+    }
+
+    /// Creates a game at the given position, with no history.
+    pub(crate) fn from_position(
+        board: MegaBoard,
+        current_player: Square,
+        current_board: Option<usize>,
+        turns: usize,
+    ) -> Game {
+        let mut game = Game {
+            board,
+            current_player,
+            current_board,
+            turns,
+            ..Default::default()
+        };
+        game.hash = zobrist::hash(&game);
+        game
+    }
+
     /// Play a move on board at pos. Returns true if the game was ended.
     pub fn play(&mut self, board_pos: usize, pos: usize) -> Result<bool, MyError> {
+        if !self.playable() {
+            return Err(GameOver);
+        }
         if let Some(current_board) = self.current_board {
             if current_board != board_pos {
                 return Err(WrongBoard);
@@ -85,13 +157,21 @@ impl Game {
         };
 
         // Play the turn
-        if self.board.play(board_pos, pos, self.current_player)? {
-            // and the game is won! Congrats to `self.turn`.
-            return Ok(true);
-        }
+        let over = self.board.play(board_pos, pos, self.current_player)?;
 
-        // No one won, so jump to the next board
-        self.current_board = if self.board[pos].playable() {
+        self.history[self.moves] = Played {
+            board_pos: board_pos as u8,
+            square_pos: pos as u8,
+            current_board: self.current_board.map(|b| b as u8),
+        };
+        self.moves += 1;
+
+        self.hash ^= zobrist::square(board_pos, pos, self.current_player)
+            ^ zobrist::player(self.current_player)
+            ^ zobrist::current_board(self.current_board);
+
+        // Jump to the next board, unless the game is over.
+        self.current_board = if !over && self.board.open() & (1 << pos) != 0 {
             Some(pos)
         } else {
             None
@@ -105,7 +185,91 @@ impl Game {
         };
         self.turns += 1;
 
-        Ok(false)
+        self.hash ^=
+            zobrist::player(self.current_player) ^ zobrist::current_board(self.current_board);
+
+        Ok(over)
+    }
+
+    /// Take back the last move played, returning its (board_pos, square_pos).
+    pub fn undo(&mut self) -> Result<(usize, usize), MyError> {
+        if self.moves == 0 {
+            return Err(NothingToUndo);
+        }
+
+        self.moves -= 1;
+        let played = core::mem::take(&mut self.history[self.moves]);
+        let board_pos = played.board_pos as usize;
+        let square_pos = played.square_pos as usize;
+
+        self.hash ^=
+            zobrist::player(self.current_player) ^ zobrist::current_board(self.current_board);
+
+        self.current_player = self.board.undo(board_pos, square_pos)?;
+        self.current_board = played.current_board.map(|b| b as usize);
+        self.turns -= 1;
+
+        self.hash ^= zobrist::square(board_pos, square_pos, self.current_player)
+            ^ zobrist::player(self.current_player)
+            ^ zobrist::current_board(self.current_board);
+
+        Ok((board_pos, square_pos))
+    }
+
+    /// Returns this game with the symmetry applied to every board, square and move.
+    pub fn transform(&self, symmetry: Symmetry) -> Game {
+        let apply = |pos: u8| symmetry.apply(pos as usize) as u8;
+
+        let mut game = Game {
+            board: self.board.transform(symmetry),
+            current_board: self.current_board.map(|b| symmetry.apply(b)),
+            ..*self
+        };
+        for played in game.history[..game.moves].iter_mut() {
+            played.board_pos = apply(played.board_pos);
+            played.square_pos = apply(played.square_pos);
+            played.current_board = played.current_board.map(apply);
+        }
+        game.hash = zobrist::hash(&game);
+        game
+    }
+
+    /// Returns true if the position is the same, ignoring how it was reached.
+    pub fn same_position(&self, other: &Game) -> bool {
+        self.board == other.board
+            && self.current_player == other.current_player
+            && self.current_board == other.current_board
+    }
+
+    /// Returns all the legal (board_pos, square_pos) moves, ordered by board then square.
+    pub fn legal_moves(&self) -> impl Iterator<Item = (usize, usize)> {
+        let mut empty = [0u16; 9];
+        for board_pos in bitboard::positions(self.legal_boards()) {
+            empty[board_pos] = self.board[board_pos].empty();
+        }
+
+        (0..9).flat_map(move |board_pos| {
+            bitboard::positions(empty[board_pos]).map(move |square_pos| (board_pos, square_pos))
+        })
+    }
+
+    /// Returns the bitmask of boards the current player may play on.
+    pub(crate) fn legal_boards(&self) -> u16 {
+        if !self.playable() {
+            return 0;
+        }
+
+        match self.current_board {
+            Some(board_pos) => 1 << board_pos,
+            None => self.board.open(),
+        }
+    }
+
+    /// Returns the (board_pos, square_pos) of each move played so far, in order.
+    pub fn history(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.history[..self.moves]
+            .iter()
+            .map(|p| (p.board_pos as usize, p.square_pos as usize))
     }
 
     pub fn board(&self, board_pos: usize) -> Result<&Board, MyError> {
@@ -115,6 +279,11 @@ impl Game {
         Ok(&self.board[board_pos])
     }
 
+    /// Returns the board of boards.
+    pub fn megaboard(&self) -> &MegaBoard {
+        &self.board
+    }
+
     pub fn square(&self, board_pos: usize, square_pos: usize) -> Result<Square, MyError> {
         if board_pos >= self.board.len() {
             return Err(InvalidBoard);
@@ -142,6 +311,33 @@ impl Game {
     pub fn turns(&self) -> usize {
         self.turns
     }
+
+    /// Returns the Zobrist hash of the position, covering the squares, the
+    /// player to move and the forced board.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns the outcome of the game, or InProgress if it is still being played.
+    pub fn result(&self) -> GameResult {
+        if self.playable() {
+            return GameResult::InProgress;
+        }
+
+        match self.winner() {
+            Square::O => GameResult::OWins,
+            Square::X => GameResult::XWins,
+            Square::None => GameResult::Draw,
+        }
+    }
+
+    /// Returns true if the current player may play at square_pos on board_pos.
+    pub fn is_legal(&self, board_pos: usize, square_pos: usize) -> bool {
+        board_pos < 9
+            && square_pos < 9
+            && self.legal_boards() & (1 << board_pos) != 0
+            && self.board[board_pos].empty() & (1 << square_pos) != 0
+    }
 }
 
 /// Extra methods only for the wasm version.
@@ -159,6 +355,30 @@ impl Game {
         Ok(self.play(board_pos, square_pos)?)
     }
 
+    /// Wrapper around [with_rules].
+    #[wasm_bindgen(js_name = with_rules)]
+    pub fn with_rules_js(rules: Rules) -> Game {
+        Game::with_rules(rules)
+    }
+
+    /// Wrapper around [rules].
+    #[wasm_bindgen(js_name = rules)]
+    pub fn rules_js(&self) -> Rules {
+        self.rules()
+    }
+
+    /// Wrapper around [undo] to return JsError.
+    #[wasm_bindgen(js_name = undo)]
+    pub fn undo_js(&mut self) -> Result<Pos, JsError> {
+        Ok(self.undo()?.into())
+    }
+
+    /// Wrapper around [legal_moves] to return an array.
+    #[wasm_bindgen(js_name = legal_moves)]
+    pub fn legal_moves_js(&self) -> Vec<Pos> {
+        self.legal_moves().map(Pos::from).collect()
+    }
+
     /// Wrapper around [board] to return JsError.
     #[wasm_bindgen(js_name = board)]
     pub fn board_js(&self, board_pos: usize) -> Result<Board, JsError> {
@@ -190,9 +410,12 @@ impl Game {
 mod tests {
     use crate::error::MyError;
     use crate::Game;
+    use crate::GameResult;
     use crate::PossibleMoves;
     use crate::Square;
     use crate::Winner;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     /*
         /// Win the specific board in specific order.
@@ -277,4 +500,130 @@ mod tests {
 
         assert_eq!(g.current_board(), None);
     }
+
+    #[test]
+    fn game_over() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for _ in 0..100 {
+            let mut g = Game::default();
+            while g.playable() {
+                assert_eq!(g.result(), GameResult::InProgress);
+
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+                let last_player = g.current_player();
+
+                if g.play(board_pos, square_pos).unwrap() {
+                    // The final move still counts as a turn.
+                    assert_ne!(g.current_player(), last_player);
+                }
+            }
+
+            let expected = match g.winner() {
+                Square::O => GameResult::OWins,
+                Square::X => GameResult::XWins,
+                Square::None => GameResult::Draw,
+            };
+            assert_eq!(g.result(), expected);
+            assert_eq!(g.turns(), g.history().count());
+            assert_eq!(g.current_board(), None);
+
+            // Every move is now rejected.
+            for board_pos in 0..9 {
+                for square_pos in 0..9 {
+                    assert_eq!(g.play(board_pos, square_pos), Err(MyError::GameOver));
+                }
+            }
+
+            // Undo and the game carries on.
+            g.undo().unwrap();
+            assert_eq!(g.result(), GameResult::InProgress);
+            assert!(g.legal_moves().count() > 0);
+        }
+    }
+
+    #[test]
+    fn legal_moves() {
+        let g = &mut Game::default();
+        assert_eq!(g.legal_moves().count(), 81);
+        assert!(g.is_legal(0, 0));
+        assert!(!g.is_legal(9, 0));
+        assert!(!g.is_legal(0, 9));
+
+        g.play(0, 1).unwrap();
+        let moves: Vec<_> = g.legal_moves().collect();
+        assert_eq!(moves, (0..9).map(|s| (1, s)).collect::<Vec<_>>());
+        assert!(g.is_legal(1, 0));
+        assert!(!g.is_legal(0, 0));
+
+        g.play(1, 0).unwrap();
+        assert_eq!(g.legal_moves().count(), 8);
+        assert!(!g.is_legal(0, 1));
+    }
+
+    #[test]
+    fn legal_moves_match_play() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for _ in 0..100 {
+            let mut g = Game::default();
+
+            while g.playable() {
+                for board_pos in 0..9 {
+                    for square_pos in 0..9 {
+                        let legal = g.is_legal(board_pos, square_pos);
+                        assert_eq!(legal, g.legal_moves().any(|m| m == (board_pos, square_pos)));
+                        let mut copy = g;
+                        assert_eq!(legal, copy.play(board_pos, square_pos).is_ok());
+                    }
+                }
+
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+                g.play(board_pos, square_pos).unwrap();
+            }
+
+            assert_eq!(g.legal_moves().count(), 0);
+        }
+    }
+
+    #[test]
+    fn undo_nothing() {
+        let g = &mut Game::default();
+        assert_eq!(g.undo(), Err(MyError::NothingToUndo));
+
+        g.play(4, 4).unwrap();
+        assert_eq!(g.undo(), Ok((4, 4)));
+        assert_eq!(g, &Game::default());
+        assert_eq!(g.undo(), Err(MyError::NothingToUndo));
+    }
+
+    #[test]
+    fn undo_round_trip() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let mut g = Game::default();
+            let mut states = vec![g];
+
+            while g.playable() {
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+
+                g.play(board_pos, square_pos).unwrap();
+                states.push(g);
+            }
+
+            let history: Vec<_> = g.history().collect();
+            assert_eq!(history.len(), states.len() - 1);
+
+            // Unwind the whole game, checking each state matches.
+            for (i, expected) in states.iter().rev().skip(1).enumerate() {
+                assert_eq!(g.undo(), Ok(history[history.len() - 1 - i]));
+                assert_eq!(&g, expected);
+            }
+            assert_eq!(g.undo(), Err(MyError::NothingToUndo));
+        }
+    }
 }
@@ -1,21 +1,41 @@
 pub mod ai;
 #[cfg(feature = "wasm")]
 pub mod ai_wasm;
+pub mod alphabeta;
+pub mod bitboard;
 pub mod board;
+pub mod book;
 pub mod error;
 pub mod game;
+pub mod mcts;
 pub mod megaboard;
 pub mod monte_carlo;
+pub mod notation;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod perft;
+pub mod playout;
+#[cfg(feature = "parallel")]
+pub mod ponder;
+pub mod record;
+pub mod rules;
+pub mod solver;
 pub mod square;
+pub mod symmetry;
+pub mod transposition;
+pub mod zobrist;
 
 #[cfg(feature = "wasm")]
 pub mod wasm;
+#[cfg(feature = "wasm")]
+pub mod worker;
 
 extern crate cfg_if;
 
 pub use board::*;
 pub use game::*;
 pub use megaboard::*;
+pub use rules::*;
 pub use square::*;
 
 pub trait Winner {
@@ -63,6 +63,7 @@ fn many_games() -> Result<(), MyError> {
     Ok(())
 }
 
+#[allow(dead_code)]
 fn monte_carlo() -> Result<(), MyError> {
     let r = &mut ChaCha8Rng::from_rng(rand::thread_rng()).unwrap();
 
@@ -82,8 +83,8 @@ fn monte_carlo() -> Result<(), MyError> {
     g.play(1, 0)?;
 
     loop {
-        let mut g = g.clone(); // Reset
-        assert_eq!(me, g.current_turn());
+        let mut g = g; // Reset
+        assert_eq!(me, g.current_player());
         assert!(g.playable());
 
         let mega_move = g.choose(r).unwrap();
@@ -101,7 +102,7 @@ fn monte_carlo() -> Result<(), MyError> {
             g.play(mega_move, board_move)?;
         }
 
-        let mut stat = &mut stats.board[mega_move][board_move];
+        let stat = &mut stats.board[mega_move][board_move];
         if g.winner() == me {
             stat.wins += 1;
         } else if g.winner() != Square::None {
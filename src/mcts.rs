@@ -0,0 +1,763 @@
+use crate::ai::Budget;
+use crate::ai::Move;
+use crate::ai::Ponder;
+use crate::ai::Score;
+use crate::ai::SearchInfo;
+use crate::ai::AI;
+use crate::error::AiError;
+use crate::monte_carlo::MegaBoardStats;
+use crate::monte_carlo::Stats;
+use crate::playout::PlayoutPolicy;
+use crate::playout::Uniform;
+use crate::Game;
+use crate::PossibleMoves;
+use crate::Square;
+use crate::Winner;
+use core::time::Duration;
+use instant::Instant; // Works on wasm instead of use std::time::Instant;
+use rand::RngCore;
+
+/// The number of playouts between checks of the time, in [MctsAI::ponder_for],
+/// or for being stopped when pondering on a background thread.
+pub(crate) const PONDER_STEP: usize = 100;
+
+/// The exploration constant used by default, √2 is the theoretical value for
+/// rewards between 0 and 1.
+pub const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// How much weight the All-Moves-As-First (AMAF) results of a move get,
+/// compared to its own results, in RAVE (Rapid Action Value Estimation).
+///
+/// AMAF results count every playout where the same player played the move
+/// later on, so they build up quickly but are biased. The schedules give them
+/// less weight as the move's own results build up.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum RaveSchedule {
+    /// Only use the move's own results.
+    #[default]
+    Off,
+
+    /// β = √(k / (3n + k)), where n is the move's visits, so the results are
+    /// weighted equally after k visits.
+    Equivalence(f64),
+
+    /// β = (k - n) / k, so AMAF results are ignored after k visits.
+    Linear(usize),
+}
+
+impl RaveSchedule {
+    /// Returns the weight of the AMAF results, for a move visited n times.
+    fn beta(&self, n: usize) -> f64 {
+        match *self {
+            RaveSchedule::Off => 0.0,
+            RaveSchedule::Equivalence(k) => (k / (3.0 * n as f64 + k)).sqrt(),
+            RaveSchedule::Linear(k) => k.saturating_sub(n) as f64 / k.max(1) as f64,
+        }
+    }
+}
+
+/// A node in the search tree, reached by playing a move from its parent.
+#[derive(Debug, Default, Clone)]
+struct Node {
+    board_pos: u8,
+    square_pos: u8,
+
+    /// The children are stored contiguously from `first_child`. Only valid
+    /// once the node has been expanded.
+    first_child: u32,
+    num_children: u8,
+    expanded: bool,
+
+    /// Every position below this node is in the tree, so searching it can't
+    /// add any more nodes.
+    complete: bool,
+
+    /// Results from the point of view of the player who played this node's move.
+    stats: Stats,
+
+    /// Results of the playouts through the parent where the same player
+    /// played this node's move at any later point.
+    amaf: Stats,
+}
+
+impl Node {
+    fn children(&self) -> core::ops::Range<usize> {
+        let first = self.first_child as usize;
+        first..first + self.num_children as usize
+    }
+
+    /// Returns the score used to pick which child to explore, from the
+    /// parent's number of visits.
+    fn ucb(&self, ln_parent: f64, exploration: f64, rave: RaveSchedule) -> f64 {
+        if self.stats.totals == 0 {
+            return f64::INFINITY;
+        }
+
+        let mut v = self.stats.expected();
+        if self.amaf.totals > 0 {
+            let beta = rave.beta(self.stats.totals);
+            v = (1.0 - beta) * v + beta * self.amaf.expected();
+        }
+
+        let n = self.stats.totals as f64;
+        v + exploration * (ln_parent / n).sqrt()
+    }
+
+    /// Returns the move as an index from 0 to 80.
+    fn index(&self) -> usize {
+        self.board_pos as usize * 9 + self.square_pos as usize
+    }
+}
+
+/// Picks the next move using Monte Carlo Tree Search, guided by UCB1 (UCT).
+///
+/// Each iteration walks down the tree picking the child with the best upper
+/// confidence bound, expands the leaf, plays out a game from there with the
+/// playout policy, and records the result in every node along the path.
+///
+/// The tree is kept between calls to [AI::choose], and if the game continued
+/// from the last searched position, the search carries on from the node
+/// reached by the moves played since.
+pub struct MctsAI<P: PlayoutPolicy = Uniform> {
+    budget: Budget,
+
+    /// Picks the moves played out from each new leaf.
+    policy: P,
+
+    /// Higher values explore less visited moves more often.
+    exploration: f64,
+
+    rave: RaveSchedule,
+
+    /// The search tree, with the root at index 0.
+    nodes: Vec<Node>,
+
+    /// The position at the root of the tree, if there is one.
+    root: Option<Game>,
+
+    pub last_results: MegaBoardStats,
+
+    /// The AMAF results for each move, as used by RAVE.
+    pub last_rave: MegaBoardStats,
+}
+
+impl MctsAI<Uniform> {
+    pub fn new(time_limit: Duration) -> Self {
+        Self::with_budget(Budget::Time(time_limit))
+    }
+
+    pub fn with_budget(budget: Budget) -> Self {
+        MctsAI {
+            budget,
+            policy: Uniform,
+            exploration: DEFAULT_EXPLORATION,
+            nodes: Vec::new(),
+            rave: RaveSchedule::Off,
+            root: None,
+            last_results: MegaBoardStats::default(),
+            last_rave: MegaBoardStats::default(),
+        }
+    }
+}
+
+impl<P: PlayoutPolicy> MctsAI<P> {
+    /// Sets how moves are picked during playouts. This starts a new tree.
+    pub fn with_policy<Q: PlayoutPolicy>(self, policy: Q) -> MctsAI<Q> {
+        MctsAI {
+            budget: self.budget,
+            policy,
+            exploration: self.exploration,
+            rave: self.rave,
+            nodes: Vec::new(),
+            root: None,
+            last_results: MegaBoardStats::default(),
+            last_rave: MegaBoardStats::default(),
+        }
+    }
+
+    /// Sets the exploration constant C in UCB1.
+    pub fn with_exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    /// Sets how AMAF results are blended with each move's own results.
+    pub fn with_rave(mut self, rave: RaveSchedule) -> Self {
+        self.rave = rave;
+        self
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    pub fn exploration(&self) -> f64 {
+        self.exploration
+    }
+
+    pub fn rave(&self) -> RaveSchedule {
+        self.rave
+    }
+
+    /// Returns the number of nodes in the search tree.
+    pub fn tree_size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Moves the root of the tree to the node reached by the moves played
+    /// since the last search, keeping the results below it. Starts a new tree
+    /// if the game didn't continue from the root, or a move isn't in the tree.
+    fn advance(&mut self, game: &Game) {
+        if let Some(node) = self.find(game) {
+            self.reroot(node);
+        } else {
+            self.nodes.clear();
+            self.nodes.push(Node::default());
+        }
+        self.root = Some(*game);
+    }
+
+    /// Returns the node for the game, by following the moves played since the root.
+    fn find(&self, game: &Game) -> Option<usize> {
+        let root = self.root.as_ref()?;
+
+        let played = root.history().count();
+        if !game.history().take(played).eq(root.history()) {
+            return None;
+        }
+
+        let mut g = *root;
+        let mut node = 0;
+        for (board_pos, square_pos) in game.history().skip(played) {
+            node = self.nodes[node].children().find(|&child| {
+                let n = &self.nodes[child];
+                (n.board_pos as usize, n.square_pos as usize) == (board_pos, square_pos)
+            })?;
+            g.play(board_pos, square_pos).ok()?;
+        }
+
+        // The history matched, but check in case the game was set up differently.
+        if !g.same_position(game) {
+            return None;
+        }
+
+        Some(node)
+    }
+
+    /// Replaces the tree with the subtree below the node.
+    fn reroot(&mut self, node: usize) {
+        if node == 0 {
+            return;
+        }
+
+        let old = std::mem::take(&mut self.nodes);
+        self.nodes.push(old[node].clone());
+
+        // Copy breadth first, so each node's children stay contiguous.
+        let mut i = 0;
+        while i < self.nodes.len() {
+            let first_child = self.nodes.len();
+            let children = self.nodes[i].children();
+            self.nodes.extend(old[children].iter().cloned());
+            self.nodes[i].first_child = first_child as u32;
+            i += 1;
+        }
+    }
+
+    /// Adds a child to the node for every move. At the root, symmetric moves
+    /// are skipped as they would have the same results.
+    fn expand(&mut self, node: usize, game: &Game) {
+        let moves: Vec<(usize, usize)> = if node == 0 {
+            game.distinct_moves()
+        } else {
+            game.legal_moves().collect()
+        };
+        self.add_children(node, &moves);
+    }
+
+    /// Adds a child to the node for each of the moves.
+    fn add_children(&mut self, node: usize, moves: &[(usize, usize)]) {
+        let first_child = self.nodes.len();
+        self.nodes
+            .extend(moves.iter().map(|&(board_pos, square_pos)| Node {
+                board_pos: board_pos as u8,
+                square_pos: square_pos as u8,
+                ..Default::default()
+            }));
+
+        let n = &mut self.nodes[node];
+        n.first_child = first_child as u32;
+        n.num_children = moves.len() as u8;
+        n.expanded = true;
+    }
+
+    /// Returns the child with the highest upper confidence bound.
+    fn select(&self, node: usize) -> Option<usize> {
+        let ln_parent = (self.nodes[node].stats.totals.max(1) as f64).ln();
+
+        self.nodes[node].children().max_by(|&a, &b| {
+            let a = self.nodes[a].ucb(ln_parent, self.exploration, self.rave);
+            let b = self.nodes[b].ucb(ln_parent, self.exploration, self.rave);
+            a.partial_cmp(&b).unwrap()
+        })
+    }
+
+    /// Runs one iteration of selection, expansion, playout and backpropagation.
+    ///
+    /// `path` and `moves` are reused between iterations to avoid allocating.
+    fn iterate<R: RngCore>(
+        &mut self,
+        r: &mut R,
+        game: &Game,
+        path: &mut Vec<usize>,
+        moves: &mut Vec<usize>,
+    ) {
+        let mut g = *game;
+        path.clear();
+        path.push(0);
+        moves.clear();
+
+        // Selection, stopping after expanding a new node.
+        let mut node = 0;
+        loop {
+            let expanded = self.nodes[node].expanded;
+            if !expanded && g.playable() {
+                self.expand(node, &g);
+            }
+
+            let child = match self.select(node) {
+                Some(child) => child,
+                None => {
+                    // The game is over.
+                    self.nodes[node].complete = true;
+                    break;
+                }
+            };
+
+            let n = &self.nodes[child];
+            g.play(n.board_pos as usize, n.square_pos as usize)
+                .expect("valid tree move");
+            moves.push(n.index());
+            path.push(child);
+            node = child;
+
+            if !expanded {
+                break;
+            }
+        }
+
+        // Playout
+        while g.playable() {
+            let (board_pos, square_pos) = self.policy.choose(r, &g);
+            g.play(board_pos, square_pos).expect("valid play");
+            moves.push(board_pos * 9 + square_pos);
+        }
+
+        // Backpropagation
+        let me = game.current_player();
+        let winner = g.winner();
+        for (depth, &node) in path.iter().enumerate() {
+            // The root's move was played by the opponent, then players alternate.
+            let mover_is_me = depth % 2 == 1;
+
+            let stats = &mut self.nodes[node].stats;
+            if winner != Square::None {
+                if (winner == me) == mover_is_me {
+                    stats.wins += 1;
+                } else {
+                    stats.loses += 1;
+                }
+            }
+            stats.totals += 1;
+        }
+
+        // A node is complete once all its children are.
+        for depth in (1..path.len()).rev() {
+            let parent = path[depth - 1];
+            if !self.nodes[path[depth]].complete
+                || !self.nodes[parent]
+                    .children()
+                    .all(|child| self.nodes[child].complete)
+            {
+                break;
+            }
+            self.nodes[parent].complete = true;
+        }
+
+        // Each square is only played once, so a move was played later by the
+        // same player if it's in the set of moves made at the same parity.
+        let mut later = [0u128; 2];
+        for (depth, &m) in moves.iter().enumerate().rev() {
+            later[depth % 2] |= 1 << m;
+
+            if depth >= path.len() {
+                continue;
+            }
+
+            // The children's moves are played by the player to move at this depth.
+            let mover_is_me = depth % 2 == 0;
+            for child in self.nodes[path[depth]].children() {
+                let n = &mut self.nodes[child];
+                if later[depth % 2] & (1 << n.index()) == 0 {
+                    continue;
+                }
+
+                if winner != Square::None {
+                    if (winner == me) == mover_is_me {
+                        n.amaf.wins += 1;
+                    } else {
+                        n.amaf.loses += 1;
+                    }
+                }
+                n.amaf.totals += 1;
+            }
+        }
+    }
+
+    /// Returns the child of the node with the most visits, if it has any.
+    fn most_visited(&self, node: usize) -> Option<usize> {
+        self.nodes[node]
+            .children()
+            .filter(|&child| self.nodes[child].stats.totals > 0)
+            .max_by_key(|&child| self.nodes[child].stats.totals)
+    }
+
+    /// Records the results of the root's children, copying them to any
+    /// symmetric moves that were skipped.
+    fn record_results(&mut self, game: &Game) {
+        self.last_results = self.root_stats(game, |n| n.stats);
+        self.last_rave = self.root_stats(game, |n| n.amaf);
+    }
+
+    fn root_stats(&self, game: &Game, f: impl Fn(&Node) -> Stats) -> MegaBoardStats {
+        let mut stats = MegaBoardStats {
+            runs: self.nodes[0].stats.totals,
+            ..Default::default()
+        };
+
+        let symmetries = game.symmetries();
+        for child in self.nodes[0].children() {
+            let n = &self.nodes[child];
+            for s in &symmetries {
+                stats.board[s.apply(n.board_pos as usize)][s.apply(n.square_pos as usize)] = f(n);
+            }
+        }
+
+        // A reused root may have every move, so its own results take priority.
+        for child in self.nodes[0].children() {
+            let n = &self.nodes[child];
+            stats.board[n.board_pos as usize][n.square_pos as usize] = f(n);
+        }
+
+        stats
+    }
+}
+
+impl<P: PlayoutPolicy> MctsAI<P> {
+    /// Ponders the position until the time has passed, checking the time every
+    /// few playouts. Returns the number of playouts done.
+    pub fn ponder_for<R: RngCore>(&mut self, r: &mut R, game: &Game, time: Duration) -> usize {
+        let start = Instant::now();
+        let mut playouts = 0;
+        loop {
+            let done = self.ponder(r, game, PONDER_STEP);
+            playouts += done;
+
+            if done == 0 || start.elapsed() > time {
+                return playouts;
+            }
+        }
+    }
+
+    /// Returns the move [AI::choose] would pick for the last position searched,
+    /// if it has been searched.
+    pub fn best(&self) -> Option<(usize, usize)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let n = &self.nodes[self.most_visited(0)?];
+        Some((n.board_pos as usize, n.square_pos as usize))
+    }
+}
+
+impl<P: PlayoutPolicy> Ponder for MctsAI<P> {
+    fn ponder<R: RngCore>(&mut self, r: &mut R, game: &Game, playouts: usize) -> usize {
+        if !game.playable() {
+            return 0;
+        }
+
+        self.advance(game);
+
+        // The opponent may play any move, not just the distinct ones, so they
+        // all need to be in the tree for it to be reused.
+        if !self.nodes[0].expanded {
+            let moves: Vec<(usize, usize)> = game.legal_moves().collect();
+            self.add_children(0, &moves);
+        }
+
+        let mut path = Vec::new();
+        let mut moves = Vec::new();
+        for _ in 0..playouts {
+            self.iterate(r, game, &mut path, &mut moves);
+        }
+
+        self.record_results(game);
+        playouts
+    }
+}
+
+impl<P: PlayoutPolicy> AI for MctsAI<P> {
+    fn choose<R: RngCore>(&mut self, mut r: R, game: &Game) -> Result<Move, AiError> {
+        if !game.playable() {
+            return Err(AiError::NoLegalMoves);
+        }
+
+        self.advance(game);
+
+        let start = Instant::now();
+        let initial_size = self.nodes.len();
+        let mut playouts = 0;
+        let mut path = Vec::new();
+        let mut moves = Vec::new();
+        loop {
+            self.iterate(&mut r, game, &mut path, &mut moves);
+            playouts += 1;
+
+            // Once the whole tree has been searched, it can't grow any more.
+            let nodes = self.nodes.len() - initial_size;
+            if self.budget.exhausted(start, playouts, nodes) || self.nodes[0].complete {
+                break;
+            }
+        }
+
+        self.record_results(game);
+
+        // Pick the most visited move, as the most robust choice.
+        let best = self.most_visited(0).unwrap();
+        let n = &self.nodes[best];
+        let (board_pos, square_pos) = (n.board_pos as usize, n.square_pos as usize);
+
+        // Follow the most visited moves, until the tree runs out.
+        let mut pv = Vec::new();
+        let mut node = Some(best);
+        while let Some(i) = node {
+            pv.push((
+                self.nodes[i].board_pos as usize,
+                self.nodes[i].square_pos as usize,
+            ));
+            node = self.most_visited(i);
+        }
+
+        Ok(Move::new(board_pos, square_pos).with_info(SearchInfo {
+            score: Score::Expected(self.nodes[best].stats.expected()),
+            pv,
+            nodes: self.nodes.len() - initial_size,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::ai::Ponder;
+    use crate::ai::Score;
+    use crate::ai::AI;
+    use crate::mcts::MctsAI;
+    use crate::mcts::RaveSchedule;
+    use crate::Game;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::time::Duration;
+
+    #[test]
+    fn empty_board() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let g = Game::default();
+        let mut ai = MctsAI::with_budget(Budget::Playouts(2000));
+
+        let (board_pos, square_pos) = ai.choose(&mut rng, &g).unwrap().pos();
+        assert!(g.is_legal(board_pos, square_pos));
+
+        // Every move has results, even those skipped by symmetry.
+        assert!(ai.last_results.board.iter().flatten().all(|s| s.totals > 0));
+        assert!(ai.last_results.runs > 0);
+
+        let board = &ai.last_results.board;
+        assert_eq!(board[0][0].totals, board[8][8].totals);
+        assert_eq!(board[0][1].totals, board[6][3].totals);
+    }
+
+    #[test]
+    fn reuse() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut g = Game::default();
+        let mut ai = MctsAI::with_budget(Budget::Playouts(2000));
+
+        let (board_pos, square_pos) = ai.choose(&mut rng, &g).unwrap().pos();
+        g.play(board_pos, square_pos).unwrap();
+        let reply = g.legal_moves().next().unwrap();
+        g.play(reply.0, reply.1).unwrap();
+
+        // The results below the reply are kept.
+        let node = ai.find(&g).unwrap();
+        let visits = ai.nodes[node].stats.totals;
+        assert!(visits > 0);
+
+        let size = ai.tree_size();
+        ai.advance(&g);
+        assert_eq!(ai.nodes[0].stats.totals, visits);
+        assert!(ai.tree_size() < size);
+
+        for (i, n) in ai.nodes.iter().enumerate() {
+            assert!(!n.expanded || n.children().start > i);
+            assert!(n.children().end <= ai.tree_size());
+        }
+
+        // A different game starts again.
+        ai.advance(&Game::default());
+        assert_eq!(ai.tree_size(), 1);
+
+        let (board_pos, square_pos) = ai.choose(&mut rng, &g).unwrap().pos();
+        assert!(g.is_legal(board_pos, square_pos));
+    }
+
+    #[test]
+    fn deterministic() {
+        let g = Game::default();
+
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut ai = MctsAI::with_budget(Budget::Playouts(500));
+
+            let m = ai.choose(&mut rng, &g).unwrap().pos();
+            results.push((m, ai.tree_size()));
+            assert_eq!(ai.last_results.runs, 500);
+        }
+        assert_eq!(results[0], results[1]);
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = MctsAI::with_budget(Budget::Nodes(1000));
+        let info = ai.choose(&mut rng, &g).unwrap().info.unwrap();
+        assert!(ai.tree_size() >= 1000);
+        assert!(info.nodes >= 1000);
+    }
+
+    #[test]
+    fn ponder() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut g = Game::default();
+        g.play(4, 4).unwrap();
+
+        // Pondering while the opponent thinks builds the tree below their moves.
+        let mut ai = MctsAI::with_budget(Budget::Playouts(100));
+        assert_eq!(ai.ponder(&mut rng, &g, 2000), 2000);
+        assert_eq!(ai.last_results.runs, 2000);
+
+        g.play(4, 0).unwrap();
+        let node = ai.find(&g).unwrap();
+        let visits = ai.nodes[node].stats.totals;
+        assert!(visits > 0);
+
+        // Including for moves that are symmetric to another.
+        let mut mirrored = g;
+        mirrored.undo().unwrap();
+        mirrored.play(4, 8).unwrap();
+        assert!(ai.nodes[ai.find(&mirrored).unwrap()].stats.totals > 0);
+
+        // The next choice reuses that work.
+        ai.choose(&mut rng, &g).unwrap();
+        assert_eq!(ai.last_results.runs, visits + 100);
+
+        // There's nothing to search once the game is over.
+        let over = Game::from_notation(
+            "OOOXX..../OOOXX..../OOOXX..../..X....../X......../........./........./........./......... X - 17",
+        )
+        .unwrap();
+        assert_eq!(ai.ponder(&mut rng, &over, 100), 0);
+        assert_eq!(ai.ponder_for(&mut rng, &over, Duration::from_secs(60)), 0);
+    }
+
+    #[test]
+    fn ponder_for() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO.XX..../..X....../X......../........./........./........./......... O 2 16",
+        )
+        .unwrap();
+
+        let mut ai = MctsAI::new(Duration::from_secs(1));
+        assert_eq!(ai.best(), None);
+
+        // However long it takes, at least one step is searched.
+        let playouts = ai.ponder_for(&mut rng, &g, Duration::ZERO);
+        assert!(playouts > 0);
+        assert_eq!(ai.last_results.runs, playouts);
+        assert!(ai.best().is_some());
+
+        let mut ai = MctsAI::new(Duration::from_secs(1));
+        assert_eq!(ai.ponder(&mut rng, &g, 2000), 2000);
+        assert_eq!(ai.best(), Some((2, 2)));
+    }
+
+    #[test]
+    fn rave() {
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO.XX..../..X....../X......../........./........./........./......... O 2 16",
+        )
+        .unwrap();
+
+        for schedule in [RaveSchedule::Equivalence(500.0), RaveSchedule::Linear(100)] {
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut ai = MctsAI::with_budget(Budget::Playouts(2000)).with_rave(schedule);
+            assert_eq!(ai.choose(&mut rng, &g).unwrap().pos(), (2, 2));
+
+            // Every time a move is played first, it's also played at some point.
+            for (rave, stats) in ai.last_rave.board.iter().zip(ai.last_results.board.iter()) {
+                for (rave, stats) in rave.iter().zip(stats.iter()) {
+                    assert!(rave.totals >= stats.totals);
+                }
+            }
+            assert!(ai.last_rave.board[2][5].totals > ai.last_results.board[2][5].totals);
+        }
+
+        assert_eq!(RaveSchedule::Off.beta(0), 0.0);
+        assert_eq!(RaveSchedule::Equivalence(10.0).beta(0), 1.0);
+        assert_eq!(RaveSchedule::Linear(10).beta(5), 0.5);
+        assert_eq!(RaveSchedule::Linear(10).beta(20), 0.0);
+    }
+
+    #[test]
+    fn node_budget_near_end() {
+        // The only move ends the game, so the tree can't reach the node budget.
+        let g = Game::from_notation(
+            "..XOOO.../XOOOXO.OX/OXOXXXO../XXOOXOXOX/OXO.XO..O/XXX.O.XXO/XOOXXXOX./..XOXXOOX/OOXXOOOX. O - 64",
+        )
+        .unwrap();
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = MctsAI::with_budget(Budget::Nodes(1000));
+        assert_eq!(ai.choose(&mut rng, &g).unwrap().pos(), (8, 8));
+        assert!(ai.nodes[0].complete);
+    }
+
+    #[test]
+    fn winning_move() {
+        // O has won boards 0 and 1, and can win board 2 and the game at square 2.
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO.XX..../..X....../X......../........./........./........./......... O 2 16",
+        )
+        .unwrap();
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = MctsAI::with_budget(Budget::Playouts(2000)).with_exploration(1.0);
+        let m = ai.choose(&mut rng, &g).unwrap();
+        assert_eq!(m.pos(), (2, 2));
+
+        // The move ends the game, so there is nothing more to follow.
+        let info = m.info.unwrap();
+        assert_eq!(info.pv, vec![(2, 2)]);
+        assert_eq!(info.score, Score::Expected(1.0));
+    }
+}
@@ -1,38 +1,75 @@
+use crate::bitboard;
 use crate::error::MyError;
 use crate::error::MyError::*;
+use crate::rules::DrawnBoards;
+use crate::rules::Rules;
+use crate::rules::TieBreak;
+use crate::symmetry::Symmetry;
 use crate::Board;
 use crate::PossibleMoves;
 use crate::Square;
 use crate::Winner;
+use core::cmp::Ordering;
 use core::fmt::Display;
 use core::fmt::Formatter;
 use core::ops::Index;
 use core::ops::IndexMut;
-use rand::prelude::SliceRandom;
 use rand::RngCore;
 use substring::Substring;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct MegaBoard {
     board: [Board; 9],
 
-    moves: Vec<usize>,
+    /// Bitmask of the boards won by O.
+    o: u16,
+
+    /// Bitmask of the boards won by X.
+    x: u16,
+
+    /// Bitmask of the boards that can no longer be played on.
+    finished: u16,
 
     /// Cache if there is a winner
     winner: Square,
+
+    rules: Rules,
 }
 
-impl Default for MegaBoard {
-    fn default() -> Self {
-        MegaBoard {
-            board: Default::default(),
-            moves: vec![0, 1, 2, 3, 4, 5, 6, 7, 8],
-            winner: Square::None,
-        }
+impl From<[Board; 9]> for MegaBoard {
+    fn from(board: [Board; 9]) -> Self {
+        MegaBoard::with_boards(board, Rules::default(), Square::None)
     }
 }
 
 impl MegaBoard {
+    pub fn with_rules(rules: Rules) -> MegaBoard {
+        MegaBoard {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a board from the state of each board, played with the rules.
+    /// The player who played last wins if a drawn board completed a line for both.
+    pub fn with_boards(board: [Board; 9], rules: Rules, last_player: Square) -> MegaBoard {
+        let mut b = MegaBoard {
+            board,
+            rules,
+            ..Default::default()
+        };
+        for pos in 0..board.len() {
+            b.update(pos);
+        }
+        b.winner = b.check_winner(last_player);
+        b
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         self.board.len()
     }
@@ -43,17 +80,90 @@ impl MegaBoard {
         }
 
         let b = self.board.index_mut(mega_pos);
-        if b.play(pos, player)? {
-            // Sub-grid has finished,
+        let finished = if self.rules.play_won_boards {
+            b.play_any(pos, player)?
+        } else {
+            b.play(pos, player)?
+        };
+
+        if finished || b.has_winner() {
+            // Sub-grid has finished (or been won),
             // Check if the larger one has been won.
-            self.winner = self.check_winner();
+            self.update(mega_pos);
+            self.winner = self.check_winner(player);
+        }
+
+        Ok(self.winner != Square::None || self.finished == bitboard::FULL)
+    }
+
+    /// Removes the play at pos on the mega_pos board, returning the player who had played there.
+    pub fn undo(&mut self, mega_pos: usize, pos: usize) -> Result<Square, MyError> {
+        if mega_pos >= self.board.len() {
+            return Err(InvalidBoard);
+        }
+
+        let player = self.board[mega_pos].undo(pos)?;
+
+        // The game was still in play before this move.
+        self.update(mega_pos);
+        self.winner = Square::None;
 
-            // Remove
-            let x = self.moves.iter().position(|&x| x == mega_pos).unwrap();
-            self.moves.swap_remove(x);
+        Ok(player)
+    }
+
+    /// Returns this board with the symmetry applied to both the boards and their squares.
+    pub fn transform(&self, symmetry: Symmetry) -> MegaBoard {
+        let mut board = [Board::default(); 9];
+        for (pos, b) in self.board.iter().enumerate() {
+            board[symmetry.apply(pos)] = b.transform(symmetry);
+        }
+
+        MegaBoard {
+            board,
+            o: symmetry.apply_mask(self.o),
+            x: symmetry.apply_mask(self.x),
+            finished: symmetry.apply_mask(self.finished),
+            ..*self
+        }
+    }
+
+    /// Updates the bitmasks to match the state of the board at mega_pos.
+    fn update(&mut self, mega_pos: usize) {
+        let b = &self.board[mega_pos];
+        let bit = 1 << mega_pos;
+
+        self.o &= !bit;
+        self.x &= !bit;
+        self.finished &= !bit;
+
+        match b.winner() {
+            Square::O => self.o |= bit,
+            Square::X => self.x |= bit,
+            Square::None => {}
         }
 
-        return Ok(self.winner != Square::None || self.moves.is_empty());
+        let finished = if self.rules.play_won_boards {
+            b.empty() == 0
+        } else {
+            !b.playable()
+        };
+        if finished {
+            self.finished |= bit;
+        }
+    }
+
+    /// Returns the bitmask of boards won by the player.
+    pub fn mask(&self, player: Square) -> u16 {
+        match player {
+            Square::O => self.o,
+            Square::X => self.x,
+            Square::None => self.finished & !(self.o | self.x),
+        }
+    }
+
+    /// Returns the bitmask of boards that can still be played on.
+    pub fn open(&self) -> u16 {
+        !self.finished & bitboard::FULL
     }
 }
 
@@ -62,11 +172,11 @@ impl PossibleMoves for MegaBoard {
     where
         R: RngCore,
     {
-        self.moves.choose(r).copied()
+        bitboard::choose(self.open(), r)
     }
 
     fn playable(&self) -> bool {
-        !self.moves.is_empty() && !self.has_winner()
+        self.open() != 0 && !self.has_winner()
     }
 }
 
@@ -86,34 +196,32 @@ impl Index<usize> for MegaBoard {
     }
 }
 
-macro_rules! check {
-    ($grid:expr, $a:literal, $b:literal, $c:literal) => {
-        let a = $grid.index($a).winner();
-        if a != Square::None {
-            if a == $grid.index($b).winner() && a == $grid.index($c).winner() {
-                return a;
+impl MegaBoard {
+    /// Returns the winner of the game, given the player who played last.
+    fn check_winner(&self, player: Square) -> Square {
+        let (o, x) = match self.rules.drawn_boards {
+            DrawnBoards::Neither => (self.o, self.x),
+            DrawnBoards::Both => {
+                let drawn = self.mask(Square::None);
+                (self.o | drawn, self.x | drawn)
             }
+        };
+
+        match (bitboard::is_win(o), bitboard::is_win(x)) {
+            // A drawn board completed a line for both.
+            (true, true) => player,
+            (true, false) => Square::O,
+            (false, true) => Square::X,
+            (false, false) if self.finished == bitboard::FULL => match self.rules.tie_break {
+                TieBreak::Draw => Square::None,
+                TieBreak::MostBoards => match self.o.count_ones().cmp(&self.x.count_ones()) {
+                    Ordering::Greater => Square::O,
+                    Ordering::Less => Square::X,
+                    Ordering::Equal => Square::None,
+                },
+            },
+            (false, false) => Square::None,
         }
-    };
-}
-
-impl MegaBoard {
-    fn check_winner(&self) -> Square {
-        // Rows
-        check!(self.board, 0, 1, 2);
-        check!(self.board, 3, 4, 5);
-        check!(self.board, 6, 7, 8);
-
-        // Cols
-        check!(self.board, 0, 3, 6);
-        check!(self.board, 1, 4, 7);
-        check!(self.board, 2, 5, 8);
-
-        // Diagonals
-        check!(self.board, 0, 4, 8);
-        check!(self.board, 2, 4, 6);
-
-        Square::None
     }
 }
 
@@ -181,7 +289,7 @@ mod tests {
     /// Win the specific board.
     fn win_board(b: &mut MegaBoard, board_pos: usize, player: Square) {
         //  We assume the board is empty.
-        assert!(b.board[board_pos].grid.iter().all(|x| x == &Square::None));
+        assert!(b.board[board_pos].grid().iter().all(|x| x == &Square::None));
 
         // ┌───────────┐
         // │ O │ O │ O │
@@ -1,5 +1,7 @@
+use crate::symmetry::Symmetry;
 use core::fmt::Display;
 use core::fmt::Formatter;
+use core::ops::AddAssign;
 use std::fmt::Write;
 use substring::Substring;
 
@@ -41,21 +43,64 @@ impl Stats {
         }
         1.0 - (self.wins + self.loses) as f64 / self.totals as f64
     }
+
+    /// Returns the average result, from 0 for a loss to 1 for a win, counting
+    /// draws as half a win.
+    pub fn expected(&self) -> f64 {
+        self.win_ratio() + self.draw_ratio() / 2.0
+    }
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.wins += other.wins;
+        self.loses += other.loses;
+        self.totals += other.totals;
+    }
+}
+
+/// Merges the results of another search of the same position.
+impl AddAssign<&MegaBoardStats> for MegaBoardStats {
+    fn add_assign(&mut self, other: &MegaBoardStats) {
+        for (board, other) in self.board.iter_mut().zip(other.board.iter()) {
+            for (stat, other) in board.iter_mut().zip(other.iter()) {
+                *stat += *other;
+            }
+        }
+        self.runs += other.runs;
+    }
 }
 
 impl MegaBoardStats {
-    pub fn best(&self) -> (usize, usize) {
+    /// Returns the played move with the best win ratio, or None if no moves
+    /// have been played.
+    pub fn best(&self) -> Option<(usize, usize)> {
         let ((board_pos, square_pos), _s) = self
             .board
             .iter()
             .enumerate()
             .flat_map(|(x, s)| s.iter().enumerate().map(move |(y, s)| ((x, y), s)))
+            // Only moves that were played, as the others may not be legal.
+            .filter(|(_, a)| a.totals > 0)
             .map(|(p, a)| (p, a.win_ratio()))
-            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
             //.max_by(|(_, a), (_, b)| a.wins.cmp(&b.wins))
-            .unwrap();
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())?;
+
+        Some((board_pos, square_pos))
+    }
 
-        (board_pos, square_pos)
+    /// Returns the stats for the same position with the symmetry applied.
+    pub fn transform(&self, symmetry: Symmetry) -> MegaBoardStats {
+        let mut stats = MegaBoardStats {
+            runs: self.runs,
+            ..Default::default()
+        };
+        for (board_pos, board) in self.board.iter().enumerate() {
+            for (square_pos, stat) in board.iter().enumerate() {
+                stats.board[symmetry.apply(board_pos)][symmetry.apply(square_pos)] = *stat;
+            }
+        }
+        stats
     }
 
     pub fn totals(&self) -> Stats {
@@ -87,9 +132,6 @@ impl MegaBoardStats {
             self.runs as f64
         };
 
-        // Find best square
-        let best = board.iter().max_by(|a, b| a.wins.cmp(&b.wins)).unwrap();
-
         // Ensure the value is only 3 wide
         let num_fmt = |stat: &Stats| {
             // Win Percentage (for the board)
@@ -106,9 +148,6 @@ impl MegaBoardStats {
             // Now trim to 3 digits
             s = s.chars().take(3).collect();
 
-            //if stat.wins == best.wins {
-            //    s = "\x1b[7m".to_owned() + &s + "\x1b[0m"
-            //}
             s
         };
 
@@ -117,7 +156,7 @@ impl MegaBoardStats {
             writeln!(
                 f,
                 "│{:>3}│{:>3}│{:>3}│",
-                num_fmt(&board[3 * row + 0]),
+                num_fmt(&board[3 * row]),
                 num_fmt(&board[3 * row + 1]),
                 num_fmt(&board[3 * row + 2]),
             )?;
@@ -176,6 +215,7 @@ impl Display for MegaBoardStats {
 
 #[cfg(test)]
 mod tests {
+    use crate::monte_carlo::MegaBoardStats;
     use crate::monte_carlo::Stats;
 
     #[test]
@@ -186,4 +226,57 @@ mod tests {
         s.lose_ratio();
         s.draw_ratio();
     }
+
+    #[test]
+    fn best() {
+        // Every played move lost, but the unplayed squares aren't picked.
+        let mut stats = MegaBoardStats::default();
+        stats.board[4][0] = Stats {
+            wins: 0,
+            loses: 2,
+            totals: 2,
+        };
+        stats.board[4][1] = Stats {
+            wins: 0,
+            loses: 1,
+            totals: 1,
+        };
+        assert_eq!(MegaBoardStats::default().best(), None);
+        assert_eq!(stats.best().unwrap().0, 4);
+
+        stats.board[4][1].wins = 1;
+        assert_eq!(stats.best(), Some((4, 1)));
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = MegaBoardStats::default();
+        a.board[0][1] = Stats {
+            wins: 2,
+            loses: 1,
+            totals: 4,
+        };
+        a.runs = 4;
+
+        let mut b = MegaBoardStats::default();
+        b.board[0][1] = Stats {
+            wins: 1,
+            loses: 0,
+            totals: 2,
+        };
+        b.board[8][8] = Stats {
+            wins: 0,
+            loses: 1,
+            totals: 1,
+        };
+        b.runs = 3;
+
+        a += &b;
+        assert_eq!(a.runs, 7);
+        assert_eq!(a.board[0][1].wins, 3);
+        assert_eq!(a.board[0][1].loses, 1);
+        assert_eq!(a.board[0][1].totals, 6);
+        assert_eq!(a.board[8][8].totals, 1);
+        assert_eq!(a.totals().totals, a.runs);
+    }
 }
@@ -0,0 +1,359 @@
+//! A compact textual notation for a [Game] position, similar to chess's FEN.
+//!
+//! The notation has four space separated fields, and a fifth for games that
+//! aren't played with the default rules:
+//!
+//! ```text
+//! OX......./.O......./........./........./........./........./........./........./......... X 1 3
+//! ```
+//!
+//! 1. The squares of each board, in board order, separated by `/`. Each board
+//!    lists its squares in order, using `O`, `X` or `.` for an empty square.
+//!    If both players have a line on a board, which is only possible when won
+//!    boards can still be played on, it's followed by the player who won it.
+//! 2. The player to move, `O` or `X`.
+//! 3. The board that must be played on, `0` to `8`, or `-` for any board.
+//! 4. The number of turns played.
+//! 5. The [Rules], such as `wt`, as written by its [Display](core::fmt::Display).
+use crate::bitboard;
+use crate::error::MyError;
+use crate::error::MyError::*;
+use crate::Board;
+use crate::Game;
+use crate::MegaBoard;
+use crate::PossibleMoves;
+use crate::Rules;
+use crate::Square;
+use crate::Winner;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+impl Game {
+    /// Returns the position in the notation described in [crate::notation].
+    pub fn to_notation(&self) -> String {
+        let boards: Vec<String> = (0..9)
+            .map(|board_pos| {
+                let b = &self[board_pos];
+                let mut row: String = b
+                    .grid()
+                    .iter()
+                    .map(|square| match square {
+                        Square::None => '.',
+                        Square::O => 'O',
+                        Square::X => 'X',
+                    })
+                    .collect();
+
+                if both_won(b) {
+                    row.push_str(&b.winner().to_string());
+                }
+                row
+            })
+            .collect();
+
+        let current_board = match self.current_board() {
+            Some(board_pos) => board_pos.to_string(),
+            None => "-".to_string(),
+        };
+
+        let mut notation = format!(
+            "{} {} {} {}",
+            boards.join("/"),
+            self.current_player(),
+            current_board,
+            self.turns()
+        );
+        if self.rules() != Rules::default() {
+            notation.push_str(&format!(" {}", self.rules()));
+        }
+        notation
+    }
+
+    /// Parses a position in the notation described in [crate::notation].
+    ///
+    /// The returned game has no history, so moves before this position can
+    /// not be undone.
+    pub fn from_notation(notation: &str) -> Result<Game, MyError> {
+        let fields: Vec<&str> = notation.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 5 {
+            return Err(InvalidNotation);
+        }
+
+        let rules = match fields.get(4) {
+            Some(rules) => rules.parse()?,
+            None => Rules::default(),
+        };
+        let board = parse_boards(fields[0], rules)?;
+
+        let current_player = match fields[1] {
+            "O" => Square::O,
+            "X" => Square::X,
+            _ => return Err(InvalidNotation),
+        };
+
+        let current_board = match fields[2] {
+            "-" => None,
+            s => match s.parse::<usize>() {
+                Ok(board_pos) if board_pos < 9 => Some(board_pos),
+                _ => return Err(InvalidNotation),
+            },
+        };
+
+        let turns = fields[3].parse::<usize>().map_err(|_| InvalidNotation)?;
+
+        let game = Game::from_position(board, current_player, current_board, turns);
+        game.validate()?;
+
+        Ok(game)
+    }
+
+    /// Checks the position could have been reached by legal play.
+    fn validate(&self) -> Result<(), MyError> {
+        let mut o = 0;
+        let mut x = 0;
+        for board_pos in 0..9 {
+            let b = &self[board_pos];
+            if both_won(b) && !self.rules().play_won_boards {
+                return Err(InvalidWinner);
+            }
+
+            o += b.mask(Square::O).count_ones() as usize;
+            x += b.mask(Square::X).count_ones() as usize;
+        }
+
+        // O always plays first.
+        let (last_player, next_player) = if o == x {
+            (Square::X, Square::O)
+        } else if o == x + 1 {
+            (Square::O, Square::X)
+        } else {
+            return Err(InvalidPieceCount);
+        };
+
+        if self.current_player() != next_player {
+            return Err(InvalidPieceCount);
+        }
+        if self.turns() != o + x {
+            return Err(InvalidTurns);
+        }
+
+        if let Some(board_pos) = self.current_board() {
+            // The last move must have been played on the square matching the forced board.
+            let played = (0..9).any(|b| self[b].mask(last_player) & (1 << board_pos) != 0);
+            let open = self.megaboard().open() & (1 << board_pos) != 0;
+            if !played || !open || !self.playable() {
+                return Err(InvalidForcedBoard);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if both players have a line on the board.
+fn both_won(b: &Board) -> bool {
+    bitboard::is_win(b.mask(Square::O)) && bitboard::is_win(b.mask(Square::X))
+}
+
+fn parse_boards(s: &str, rules: Rules) -> Result<MegaBoard, MyError> {
+    let rows: Vec<&str> = s.split('/').collect();
+    if rows.len() != 9 {
+        return Err(InvalidNotation);
+    }
+
+    let mut boards = [Board::default(); 9];
+    for (board, row) in boards.iter_mut().zip(rows) {
+        let chars: Vec<char> = row.chars().collect();
+        if chars.len() != 9 && chars.len() != 10 {
+            return Err(InvalidNotation);
+        }
+
+        let mut grid = [Square::None; 9];
+        for (square, c) in grid.iter_mut().zip(&chars) {
+            *square = match c {
+                '.' => Square::None,
+                'O' => Square::O,
+                'X' => Square::X,
+                _ => return Err(InvalidNotation),
+            };
+        }
+        *board = Board::from(grid);
+
+        // The winner is only given when it can't be worked out from the squares.
+        if let Some(winner) = chars.get(9) {
+            let winner = match winner {
+                'O' => Square::O,
+                'X' => Square::X,
+                _ => return Err(InvalidNotation),
+            };
+            if !both_won(board) {
+                return Err(InvalidNotation);
+            }
+            *board = board.with_winner(winner);
+        } else if both_won(board) {
+            return Err(InvalidWinner);
+        }
+    }
+
+    // O always plays first, so has played last if it has more pieces.
+    let (o, x) = boards.iter().fold((0, 0), |(o, x), b| {
+        (
+            o + b.mask(Square::O).count_ones(),
+            x + b.mask(Square::X).count_ones(),
+        )
+    });
+    let last_player = if o > x { Square::O } else { Square::X };
+
+    let board = MegaBoard::with_boards(boards, rules, last_player);
+    if bitboard::is_win(board.mask(Square::O)) && bitboard::is_win(board.mask(Square::X)) {
+        return Err(InvalidWinner);
+    }
+
+    Ok(board)
+}
+
+/// Extra methods only for the wasm version.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl Game {
+    /// Wrapper around [to_notation].
+    #[wasm_bindgen(js_name = to_notation)]
+    pub fn to_notation_js(&self) -> String {
+        self.to_notation()
+    }
+
+    /// Wrapper around [from_notation] to return JsError.
+    #[wasm_bindgen(js_name = from_notation)]
+    pub fn from_notation_js(notation: &str) -> Result<Game, JsError> {
+        Ok(Game::from_notation(notation)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::MyError;
+    use crate::DrawnBoards;
+    use crate::Game;
+    use crate::PossibleMoves;
+    use crate::Rules;
+    use crate::Square;
+    use crate::TieBreak;
+    use crate::Winner;
+    use rand::seq::IteratorRandom;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const EMPTY: &str =
+        "........./........./........./........./........./........./........./........./.........";
+
+    #[test]
+    fn default() {
+        let g = Game::default();
+        assert_eq!(g.to_notation(), format!("{} O - 0", EMPTY));
+        assert_eq!(Game::from_notation(&g.to_notation()), Ok(g));
+    }
+
+    #[test]
+    fn round_trip() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for _ in 0..100 {
+            let mut g = Game::default();
+
+            loop {
+                let notation = g.to_notation();
+                let parsed = Game::from_notation(&notation).unwrap();
+                assert_eq!(parsed.to_notation(), notation);
+                assert_eq!(
+                    parsed.legal_moves().collect::<Vec<_>>(),
+                    g.legal_moves().collect::<Vec<_>>()
+                );
+
+                if !g.playable() {
+                    break;
+                }
+
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+                g.play(board_pos, square_pos).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn variant_round_trip() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for rules in [
+            Rules::new(true, DrawnBoards::Neither, TieBreak::Draw),
+            Rules::new(false, DrawnBoards::Both, TieBreak::MostBoards),
+            Rules::new(true, DrawnBoards::Both, TieBreak::MostBoards),
+        ] {
+            for _ in 0..100 {
+                let mut g = Game::with_rules(rules);
+
+                loop {
+                    let notation = g.to_notation();
+                    let parsed = Game::from_notation(&notation).unwrap();
+                    assert_eq!(parsed.rules(), rules);
+                    assert_eq!(parsed.to_notation(), notation);
+                    assert_eq!(parsed.result(), g.result(), "{}", notation);
+                    assert_eq!(
+                        parsed.legal_moves().collect::<Vec<_>>(),
+                        g.legal_moves().collect::<Vec<_>>()
+                    );
+
+                    if !g.playable() {
+                        break;
+                    }
+
+                    let (board_pos, square_pos) = g.legal_moves().choose(r).unwrap();
+                    g.play(board_pos, square_pos).unwrap();
+                }
+            }
+        }
+
+        // A won board that can still be played on, where both have a line.
+        let notation = "OOOXXX...O/XO......./........./........./........./........./........./........./......... O 0 8 w";
+        let g = Game::from_notation(notation).unwrap();
+        assert_eq!(g[0].winner(), Square::O);
+        assert_eq!(g.to_notation(), notation);
+    }
+
+    #[test]
+    fn invalid() {
+        let tests = [
+            ("", MyError::InvalidNotation),
+            (EMPTY, MyError::InvalidNotation),
+            ("......../........./........./........./........./........./........./........./......... O - 0", MyError::InvalidNotation),
+            ("........./........./........./........./........./........./........./......... O - 0", MyError::InvalidNotation),
+            ("A......../........./........./........./........./........./........./........./......... O - 0", MyError::InvalidNotation),
+            ("........./........./........./........./........./........./........./........./......... None - 0", MyError::InvalidNotation),
+            ("........./........./........./........./........./........./........./........./......... O 9 0", MyError::InvalidNotation),
+            ("........./........./........./........./........./........./........./........./......... O - a", MyError::InvalidNotation),
+
+            // X can not go first.
+            ("........./........./........./........./........./........./........./........./......... X - 0", MyError::InvalidPieceCount),
+            ("X......../........./........./........./........./........./........./........./......... O - 1", MyError::InvalidPieceCount),
+            ("OO......./........./........./........./........./........./........./........./......... X 2 2", MyError::InvalidPieceCount),
+
+            ("O......../........./........./........./........./........./........./........./......... X 0 5", MyError::InvalidTurns),
+
+            // The last move was not played on square 5.
+            ("O......../........./........./........./........./........./........./........./......... X 5 1", MyError::InvalidForcedBoard),
+            // Board 0 has been won.
+            ("OOO....../XX......./........./........./........./........./........./........./......... X 0 5", MyError::InvalidForcedBoard),
+            ("........./........./........./........./........./........./........./........./......... O 0 0", MyError::InvalidForcedBoard),
+
+            ("OOOXXX.../........./........./........./........./........./........./........./......... O - 6", MyError::InvalidWinner),
+            ("OOOXXX...O/XO......./........./........./........./........./........./........./......... O 0 8", MyError::InvalidWinner),
+            ("OOO.XX..XO/........./........./........./........./........./........./........./......... X - 5 w", MyError::InvalidNotation),
+            ("........./........./........./........./........./........./........./........./......... O - 0 x", MyError::InvalidRules),
+        ];
+
+        for (notation, err) in tests {
+            assert_eq!(Game::from_notation(notation), Err(err), "{}", notation);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Monte Carlo search spread over multiple threads.
+//!
+//! Uses root parallelization, each thread runs its own independent playouts
+//! from the position, and the results are merged at the end. Threads aren't
+//! available on wasm, so this is only built with the `parallel` feature.
+use crate::ai::playout;
+use crate::ai::Budget;
+use crate::ai::Move;
+use crate::ai::Score;
+use crate::ai::SearchInfo;
+use crate::ai::AI;
+use crate::error::AiError;
+use crate::monte_carlo::MegaBoardStats;
+use crate::playout::Uniform;
+use crate::Game;
+use crate::PossibleMoves;
+use core::time::Duration;
+use instant::Instant; // Works on wasm instead of use std::time::Instant;
+use rand::RngCore;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::thread;
+
+/// Picks the next move based on MonteCarlo simulation, run on multiple threads.
+pub struct ParallelMonteCarloAI {
+    budget: Budget,
+    threads: usize,
+    pub last_results: MegaBoardStats,
+}
+
+impl ParallelMonteCarloAI {
+    /// Creates an AI using one thread per available core.
+    pub fn new(time_limit: Duration) -> Self {
+        Self::with_budget(Budget::Time(time_limit))
+    }
+
+    /// Creates an AI using one thread per available core. Playout and node
+    /// limits are shared out between the threads.
+    pub fn with_budget(budget: Budget) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+        ParallelMonteCarloAI {
+            budget,
+            threads,
+            last_results: MegaBoardStats::default(),
+        }
+    }
+
+    /// Sets the number of threads to search with.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "at least one thread is required");
+        self.threads = threads;
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    /// Returns the number of threads to search with. Each thread does at
+    /// least one playout, so there are no more threads than the playout or
+    /// node limit.
+    fn search_threads(&self) -> usize {
+        let limit = match self.budget {
+            Budget::Time(_) => usize::MAX,
+            Budget::Playouts(limit) | Budget::Nodes(limit) => limit,
+            Budget::First {
+                playouts, nodes, ..
+            } => playouts.min(nodes),
+        };
+        self.threads.min(limit).max(1)
+    }
+
+    /// Returns the budget for the ith of the threads, so the threads' budgets
+    /// add up to the whole budget.
+    fn thread_budget(&self, i: usize, threads: usize) -> Budget {
+        let share = |limit: usize| limit / threads + usize::from(i < limit % threads);
+
+        match self.budget {
+            Budget::Time(time) => Budget::Time(time),
+            Budget::Playouts(limit) => Budget::Playouts(share(limit)),
+            Budget::Nodes(limit) => Budget::Nodes(share(limit)),
+            Budget::First {
+                time,
+                playouts,
+                nodes,
+            } => Budget::First {
+                time,
+                playouts: share(playouts),
+                nodes: share(nodes),
+            },
+        }
+    }
+}
+
+impl AI for ParallelMonteCarloAI {
+    fn choose<R: RngCore>(&mut self, mut r: R, game: &Game) -> Result<Move, AiError> {
+        if !game.playable() {
+            return Err(AiError::NoLegalMoves);
+        }
+
+        // Each thread gets its own stream from the same seed, so the threads
+        // never play the same games.
+        let seed = r.next_u64();
+        let start = Instant::now();
+
+        let threads = self.search_threads();
+        let results: Vec<(MegaBoardStats, usize)> = thread::scope(|s| {
+            let handles: Vec<_> = (0..threads)
+                .map(|i| {
+                    let budget = self.thread_budget(i, threads);
+                    s.spawn(move || {
+                        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                        rng.set_stream(i as u64);
+
+                        let mut stats = MegaBoardStats::default();
+                        let mut nodes = 0;
+                        loop {
+                            nodes += playout(&mut rng, game, &Uniform, &mut stats, None);
+
+                            if budget.exhausted(start, stats.runs, nodes) {
+                                break;
+                            }
+                        }
+                        (stats, nodes)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("search thread panicked"))
+                .collect()
+        });
+
+        let mut stats = MegaBoardStats::default();
+        let mut nodes = 0;
+        for (result, n) in &results {
+            stats += result;
+            nodes += n;
+        }
+
+        let (board_pos, square_pos) = stats
+            .best()
+            .or_else(|| game.legal_moves().next())
+            .ok_or(AiError::NoLegalMoves)?;
+        let info = SearchInfo {
+            score: Score::Expected(stats.board[board_pos][square_pos].expected()),
+            pv: vec![(board_pos, square_pos)],
+            nodes,
+        };
+        self.last_results = stats;
+
+        Ok(Move::new(board_pos, square_pos).with_info(info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::ai::AI;
+    use crate::parallel::ParallelMonteCarloAI;
+    use crate::Game;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::time::Duration;
+
+    #[test]
+    fn merge() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let g = Game::default();
+        let mut ai = ParallelMonteCarloAI::new(Duration::from_millis(100)).with_threads(4);
+
+        let (board_pos, square_pos) = ai.choose(&mut rng, &g).unwrap().pos();
+        assert!(g.is_legal(board_pos, square_pos));
+
+        // Every playout from every thread is counted once.
+        let results = &ai.last_results;
+        assert!(results.runs >= 4);
+        assert_eq!(results.totals().totals, results.runs);
+        assert!(results.board.iter().flatten().all(|s| s.totals > 0));
+    }
+
+    #[test]
+    fn deterministic() {
+        let g = Game::default();
+
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut ai = ParallelMonteCarloAI::with_budget(Budget::Playouts(1001)).with_threads(4);
+
+            let m = ai.choose(&mut rng, &g).unwrap().pos();
+            assert_eq!(ai.last_results.runs, 1001);
+            results.push((m, ai.last_results.board[0][0].wins));
+        }
+        assert_eq!(results[0], results[1]);
+
+        // Small budgets aren't exceeded by giving threads nothing to do.
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut ai = ParallelMonteCarloAI::with_budget(Budget::Playouts(3)).with_threads(8);
+        ai.choose(&mut rng, &g).unwrap();
+        assert_eq!(ai.last_results.runs, 3);
+    }
+}
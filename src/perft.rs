@@ -0,0 +1,112 @@
+//! Perft (performance test) counts the leaf nodes of the legal move tree to a
+//! fixed depth. The counts can be compared against other implementations of
+//! the rules, and any change that alters move generation changes them.
+use crate::Game;
+
+impl Game {
+    /// Returns the number of move sequences of exactly `depth` moves from this
+    /// position. Sequences that end the game early are not counted.
+    pub fn perft(&self, depth: usize) -> u64 {
+        let mut g = *self;
+        perft(&mut g, depth)
+    }
+
+    /// Returns the perft of each legal move at `depth - 1`, which sum to the
+    /// perft at `depth`. Useful for finding which move differs between two
+    /// implementations.
+    pub fn perft_divide(&self, depth: usize) -> Vec<((usize, usize), u64)> {
+        assert!(depth > 0, "perft_divide requires a depth of at least 1");
+
+        let mut g = *self;
+        self.legal_moves()
+            .map(|(board_pos, square_pos)| {
+                g.play(board_pos, square_pos).expect("legal move");
+                let count = perft(&mut g, depth - 1);
+                g.undo().expect("undo legal move");
+
+                ((board_pos, square_pos), count)
+            })
+            .collect()
+    }
+}
+
+fn perft(g: &mut Game, depth: usize) -> u64 {
+    match depth {
+        0 => 1,
+        1 => g.legal_moves().count() as u64,
+        _ => {
+            let mut count = 0;
+            for (board_pos, square_pos) in g.legal_moves() {
+                g.play(board_pos, square_pos).expect("legal move");
+                count += perft(g, depth - 1);
+                g.undo().expect("undo legal move");
+            }
+            count
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Game;
+
+    /// Known perft values, starting at depth 0, for positions in notation.
+    const POSITIONS: [(&str, &[u64]); 5] = [
+        (
+            "........./........./........./........./........./........./........./........./......... O - 0",
+            &[1, 81, 720, 6336, 55080, 473256],
+        ),
+        // O has won board 0, and X sent O back there, so O can play anywhere.
+        (
+            "...OOO.../........./........./X......../X......../X......../........./........./......... O - 6",
+            &[1, 69, 884, 10935, 130566],
+        ),
+        (
+            "..OX.O.XO/..X..O.O./..OXO.OXO/OOXOX.XOO/O.OX.X.X./XXX...OXO/..XXXO.O./X.OOOO.X./.XXX.XXO. O - 50",
+            &[1, 17, 114, 806, 4897, 30354],
+        ),
+        (
+            ".XXXOOOOX/OX.O.OO.X/O.OX...OO/XX.O.OOX./OOX....../X...X.O.X/XOXX...XX/..XX.XXOO/OX.OXO.O. O 3 50",
+            &[1, 3, 13, 112, 845, 6524],
+        ),
+        (
+            "XO..OOO../XXXO.XXX./XOXOOO.XX/.OX..X..X/.OXOXOO.X/XOOX.XO.O/XX..X.O.X/.O..X.O../O.....OOO O 0 50",
+            &[1, 4, 39, 266, 1295, 6538],
+        ),
+    ];
+
+    #[test]
+    fn perft() {
+        for (notation, expected) in POSITIONS {
+            let g = Game::from_notation(notation).unwrap();
+            for (depth, &count) in expected.iter().enumerate() {
+                assert_eq!(g.perft(depth), count, "{} at depth {}", notation, depth);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore] // Slow, run with `cargo test --release -- --ignored`
+    fn perft_deep() {
+        let g = Game::default();
+        assert_eq!(g.perft(6), 4020960);
+        assert_eq!(g.perft(7), 33782544);
+    }
+
+    #[test]
+    fn divide() {
+        let g = Game::default();
+        let divide = g.perft_divide(3);
+
+        assert_eq!(divide.len(), 81);
+        assert_eq!(
+            divide.iter().map(|(_, count)| count).sum::<u64>(),
+            g.perft(3)
+        );
+
+        // Symmetric moves have the same count.
+        let count = |m| divide.iter().find(|(p, _)| *p == m).unwrap().1;
+        assert_eq!(count((0, 0)), count((8, 8)));
+        assert_eq!(count((0, 1)), count((6, 3)));
+    }
+}
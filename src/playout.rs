@@ -0,0 +1,247 @@
+//! Policies for picking moves during Monte Carlo playouts.
+//!
+//! Uniformly random playouts are fast, but play so badly that they can
+//! mislead the search. Smarter policies are slower, but each playout is closer
+//! to how the game would really go.
+use crate::bitboard;
+use crate::Game;
+use crate::PossibleMoves;
+use crate::Square;
+use crate::Winner;
+use rand::Rng;
+use rand::RngCore;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Picks the moves played during a playout.
+pub trait PlayoutPolicy {
+    /// Returns the (board_pos, square_pos) to play. The game must be playable.
+    fn choose<R: RngCore>(&self, r: &mut R, g: &Game) -> (usize, usize);
+}
+
+/// Plays uniformly random moves.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Uniform;
+
+/// Wins a board when possible, otherwise plays randomly.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TakeWins;
+
+/// Wins a board when possible, then stops the opponent winning a board,
+/// otherwise plays randomly.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockWins;
+
+/// Avoids sending the opponent to a finished board, which would let them
+/// play anywhere, otherwise plays randomly.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AvoidFreeMoves;
+
+/// Combines the other policies, in the order: take a win, block a win, then
+/// avoid giving a free move.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Greedy;
+
+/// One of the policies, picked at runtime, such as from a setting.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub enum Policy {
+    #[default]
+    Uniform,
+    TakeWins,
+    BlockWins,
+    AvoidFreeMoves,
+    Greedy,
+}
+
+impl PlayoutPolicy for Policy {
+    fn choose<R: RngCore>(&self, r: &mut R, g: &Game) -> (usize, usize) {
+        match self {
+            Policy::Uniform => Uniform.choose(r, g),
+            Policy::TakeWins => TakeWins.choose(r, g),
+            Policy::BlockWins => BlockWins.choose(r, g),
+            Policy::AvoidFreeMoves => AvoidFreeMoves.choose(r, g),
+            Policy::Greedy => Greedy.choose(r, g),
+        }
+    }
+}
+
+impl PlayoutPolicy for Uniform {
+    fn choose<R: RngCore>(&self, r: &mut R, g: &Game) -> (usize, usize) {
+        let board_pos = g.choose(r).unwrap();
+        let square_pos = g[board_pos].choose(r).unwrap();
+
+        (board_pos, square_pos)
+    }
+}
+
+impl PlayoutPolicy for TakeWins {
+    fn choose<R: RngCore>(&self, r: &mut R, g: &Game) -> (usize, usize) {
+        let me = g.current_player();
+        choose_where(r, g, |board_pos| wins(g, board_pos, me))
+            .unwrap_or_else(|| Uniform.choose(r, g))
+    }
+}
+
+impl PlayoutPolicy for BlockWins {
+    fn choose<R: RngCore>(&self, r: &mut R, g: &Game) -> (usize, usize) {
+        let me = g.current_player();
+        choose_where(r, g, |board_pos| wins(g, board_pos, me))
+            .or_else(|| choose_where(r, g, |board_pos| wins(g, board_pos, me.opponent())))
+            .unwrap_or_else(|| Uniform.choose(r, g))
+    }
+}
+
+impl PlayoutPolicy for AvoidFreeMoves {
+    fn choose<R: RngCore>(&self, r: &mut R, g: &Game) -> (usize, usize) {
+        let open = g.megaboard().open();
+        choose_where(r, g, |board_pos| g[board_pos].empty() & open)
+            .unwrap_or_else(|| Uniform.choose(r, g))
+    }
+}
+
+impl PlayoutPolicy for Greedy {
+    fn choose<R: RngCore>(&self, r: &mut R, g: &Game) -> (usize, usize) {
+        let me = g.current_player();
+        let open = g.megaboard().open();
+
+        choose_where(r, g, |board_pos| wins(g, board_pos, me))
+            .or_else(|| choose_where(r, g, |board_pos| wins(g, board_pos, me.opponent())))
+            .or_else(|| choose_where(r, g, |board_pos| g[board_pos].empty() & open))
+            .unwrap_or_else(|| Uniform.choose(r, g))
+    }
+}
+
+/// Returns the squares on the board that would complete a line for the player,
+/// if the board hasn't been won yet.
+fn wins(g: &Game, board_pos: usize, player: Square) -> u16 {
+    let board = &g[board_pos];
+    if board.has_winner() {
+        return 0;
+    }
+    bitboard::completing(board.mask(player), board.empty())
+}
+
+/// Picks uniformly from the legal moves in the squares returned for each
+/// legal board, or None if there are none.
+fn choose_where<R: RngCore>(
+    r: &mut R,
+    g: &Game,
+    squares: impl Fn(usize) -> u16,
+) -> Option<(usize, usize)> {
+    let boards = g.legal_boards();
+
+    let mut candidates = [0u16; 9];
+    let mut total = 0;
+    for board_pos in bitboard::positions(boards) {
+        candidates[board_pos] = squares(board_pos) & g[board_pos].empty();
+        total += candidates[board_pos].count_ones();
+    }
+
+    if total == 0 {
+        return None;
+    }
+
+    let mut n = r.gen_range(0..total);
+    for (board_pos, &mask) in candidates.iter().enumerate() {
+        let count = mask.count_ones();
+        if n < count {
+            return Some((board_pos, bitboard::nth(mask, n)));
+        }
+        n -= count;
+    }
+
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::playout::*;
+    use crate::Rules;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Plays random games using the policy, checking every move is legal.
+    fn play_games<P: PlayoutPolicy>(policy: P) {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+        let play_won_boards = Rules {
+            play_won_boards: true,
+            ..Default::default()
+        };
+
+        for rules in [Rules::default(), play_won_boards] {
+            for _ in 0..100 {
+                let mut g = Game::with_rules(rules);
+                while g.playable() {
+                    let (board_pos, square_pos) = policy.choose(r, &g);
+                    assert!(g.is_legal(board_pos, square_pos));
+                    g.play(board_pos, square_pos).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn legal() {
+        play_games(Uniform);
+        play_games(TakeWins);
+        play_games(BlockWins);
+        play_games(AvoidFreeMoves);
+        play_games(Greedy);
+        play_games(Policy::Greedy);
+    }
+
+    #[test]
+    fn take_and_block() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        // O can win board 2 at square 2.
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO.XX..../..X....../X......../........./........./........./......... O 2 16",
+        )
+        .unwrap();
+        for _ in 0..10 {
+            assert_eq!(TakeWins.choose(r, &g), (2, 2));
+            assert_eq!(BlockWins.choose(r, &g), (2, 2));
+        }
+
+        // X can't win board 2, but can stop O winning it.
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO..X..../..X....../X......../........./........./........./......... X 2 15",
+        )
+        .unwrap();
+        for _ in 0..10 {
+            assert_eq!(BlockWins.choose(r, &g), (2, 2));
+        }
+    }
+
+    #[test]
+    fn play_won_boards() {
+        // O must play on board 0, which O has already won, so completing
+        // another line there doesn't win anything.
+        let g = Game::from_notation(
+            "O..OOO.../X......../X......../X......../X......../........./........./........./......... O 0 8 w",
+        )
+        .unwrap();
+        assert_eq!(g.legal_boards(), 1 << 0);
+        assert_eq!(wins(&g, 0, Square::O), 0);
+        assert_eq!(wins(&g, 0, Square::X), 0);
+    }
+
+    #[test]
+    fn avoid_free_moves() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        // Boards 0 and 1 are won, so X should avoid squares 0 and 1.
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../..OXX..../........./........./........./........./........./......... X 2 13",
+        )
+        .unwrap();
+        for _ in 0..20 {
+            let (board_pos, square_pos) = AvoidFreeMoves.choose(r, &g);
+            assert_eq!(board_pos, 2);
+            assert!(square_pos > 1);
+        }
+    }
+}
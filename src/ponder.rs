@@ -0,0 +1,98 @@
+//! Pondering on a background thread, so a native AI can think while the
+//! opponent does. Threads aren't available on wasm, where [Ponder::ponder]
+//! should be called in small slices instead, so this is only built with the
+//! `parallel` feature.
+use crate::ai::Ponder;
+use crate::mcts::PONDER_STEP;
+use crate::Game;
+use rand::RngCore;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+
+/// An AI pondering a position on a background thread, until it's stopped.
+pub struct Ponderer<A, R> {
+    stop: Arc<AtomicBool>,
+    playouts: Arc<AtomicUsize>,
+    handle: JoinHandle<(A, R, usize)>,
+}
+
+impl<A, R> Ponderer<A, R>
+where
+    A: Ponder + Send + 'static,
+    R: RngCore + Send + 'static,
+{
+    /// Starts pondering the game, usually just after the AI's own move.
+    pub fn start(mut ai: A, mut r: R, game: Game) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let playouts = Arc::new(AtomicUsize::new(0));
+
+        let stopped = stop.clone();
+        let done_so_far = playouts.clone();
+        let handle = thread::spawn(move || {
+            let mut playouts = 0;
+            while !stopped.load(Ordering::Relaxed) {
+                let done = ai.ponder(&mut r, &game, PONDER_STEP);
+                if done == 0 {
+                    break; // Nothing left to search.
+                }
+                playouts += done;
+                done_so_far.store(playouts, Ordering::Relaxed);
+            }
+            (ai, r, playouts)
+        });
+
+        Ponderer {
+            stop,
+            playouts,
+            handle,
+        }
+    }
+
+    /// Returns the number of playouts done so far.
+    pub fn playouts(&self) -> usize {
+        self.playouts.load(Ordering::Relaxed)
+    }
+
+    /// Interrupts the search, such as when the opponent moves, and returns the
+    /// AI with its work, the rng, and the number of playouts done.
+    pub fn stop(self) -> (A, R, usize) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().expect("pondering thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::ai::AI;
+    use crate::mcts::MctsAI;
+    use crate::ponder::Ponderer;
+    use crate::Game;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::thread;
+
+    #[test]
+    fn stop() {
+        let mut g = Game::default();
+        g.play(4, 4).unwrap();
+
+        let ai = MctsAI::with_budget(Budget::Playouts(100));
+        let ponderer = Ponderer::start(ai, ChaCha8Rng::seed_from_u64(0), g);
+        while ponderer.playouts() < 500 {
+            thread::yield_now();
+        }
+        let (mut ai, mut rng, playouts) = ponderer.stop();
+        assert!(playouts >= 500);
+        assert_eq!(ai.last_results.runs, playouts);
+
+        // The opponent's move is already searched.
+        g.play(4, 0).unwrap();
+        ai.choose(&mut rng, &g).unwrap();
+        assert!(ai.last_results.runs > 100);
+    }
+}
@@ -0,0 +1,391 @@
+//! A PGN-style record of a whole game, with headers and the list of moves.
+//!
+//! ```text
+//! [O "Human"]
+//! [X "MonteCarloAI"]
+//! [Date "2023.01.31"]
+//! [Result "*"]
+//!
+//! 1. e5 e4 2. e2 e6 *
+//! ```
+//!
+//! Moves are written as coordinates on the full 9x9 grid, with columns `a` to
+//! `i` from left to right, and rows `1` to `9` from top to bottom. The movetext
+//! ends with the result, `1-0` if O won, `0-1` if X won, `1/2-1/2` for a draw, or
+//! `*` if the game is still in progress.
+//!
+//! Games not played with the default rules have a `Rules` header, with the
+//! [Rules] as written by its [Display].
+use crate::error::RecordError;
+use crate::Game;
+use crate::GameResult;
+use crate::Rules;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+/// Headers are written in this order, followed by any others.
+const STANDARD_HEADERS: [&str; 5] = ["O", "X", "Date", "Result", "Rules"];
+
+/// Maximum width of a line of movetext.
+const LINE_WIDTH: usize = 80;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    /// The (name, value) headers, in the order they were added.
+    headers: Vec<(String, String)>,
+
+    /// The (board_pos, square_pos) of each move, in order.
+    moves: Vec<(usize, usize)>,
+}
+
+impl GameRecord {
+    /// Creates a record of the moves played in the game so far.
+    pub fn from_game(game: &Game) -> GameRecord {
+        let mut record = GameRecord {
+            headers: Vec::new(),
+            moves: game.history().collect(),
+        };
+        record.set_header("Result", result(game));
+        if game.rules() != Rules::default() {
+            record.set_header("Rules", &game.rules().to_string());
+        }
+        record
+    }
+
+    /// Returns the value of the named header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Sets the value of the named header, such as "O", "X", or "Date".
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Returns the (board_pos, square_pos) of each move, in order.
+    pub fn moves(&self) -> &[(usize, usize)] {
+        &self.moves
+    }
+
+    /// Returns the rules the game was played with, from the `Rules` header.
+    pub fn rules(&self) -> Result<Rules, RecordError> {
+        match self.header("Rules") {
+            Some(rules) => rules
+                .parse()
+                .map_err(|_| RecordError::InvalidRules(rules.to_string())),
+            None => Ok(Rules::default()),
+        }
+    }
+
+    /// Plays all the moves with the game's rules, returning the resulting game.
+    pub fn replay(&self) -> Result<Game, RecordError> {
+        let mut game = Game::with_rules(self.rules()?);
+        for (index, &(board_pos, square_pos)) in self.moves.iter().enumerate() {
+            game.play(board_pos, square_pos)
+                .map_err(|error| RecordError::IllegalMove { index, error })?;
+        }
+        Ok(game)
+    }
+
+    /// Parses a record, and checks all the moves are legal.
+    pub fn parse(s: &str) -> Result<GameRecord, RecordError> {
+        let mut record = GameRecord::default();
+        let mut movetext = String::new();
+
+        for (line_number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                let (name, value) =
+                    parse_header(line).ok_or(RecordError::InvalidHeader(line_number + 1))?;
+                record.set_header(&name, &value);
+            } else {
+                movetext.push_str(line);
+                movetext.push(' ');
+            }
+        }
+
+        let mut result = None;
+        for token in movetext.split_whitespace() {
+            if result.is_some() {
+                // Nothing may follow the result.
+                return Err(RecordError::InvalidMove(token.to_string()));
+            }
+
+            match token {
+                "1-0" | "0-1" | "1/2-1/2" | "*" => result = Some(token),
+                _ if is_move_number(token) => {}
+                _ => {
+                    let m = from_coordinate(token)
+                        .ok_or_else(|| RecordError::InvalidMove(token.to_string()))?;
+                    record.moves.push(m);
+                }
+            }
+        }
+
+        let game = record.replay()?;
+        for claimed in [result, record.header("Result")].into_iter().flatten() {
+            if claimed != "*" && claimed != self::result(&game) {
+                return Err(RecordError::WrongResult(claimed.to_string()));
+            }
+        }
+
+        Ok(record)
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let mut headers: Vec<&(String, String)> = self.headers.iter().collect();
+        headers.sort_by_key(|(name, _)| {
+            STANDARD_HEADERS
+                .iter()
+                .position(|h| h == name)
+                .unwrap_or(STANDARD_HEADERS.len())
+        });
+
+        for (name, value) in headers {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        for (i, &(board_pos, square_pos)) in self.moves.iter().enumerate() {
+            if i % 2 == 0 {
+                tokens.push(format!("{}.", i / 2 + 1));
+            }
+            tokens.push(to_coordinate(board_pos, square_pos));
+        }
+        tokens.push(self.header("Result").unwrap_or("*").to_string());
+
+        // Wrap the movetext to keep lines short.
+        let mut width = 0;
+        for token in tokens {
+            if width > 0 && width + 1 + token.len() > LINE_WIDTH {
+                writeln!(f)?;
+                width = 0;
+            }
+            if width > 0 {
+                write!(f, " ")?;
+                width += 1;
+            }
+            write!(f, "{}", token)?;
+            width += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+/// Returns the result token for the game.
+fn result(game: &Game) -> &'static str {
+    match game.result() {
+        GameResult::InProgress => "*",
+        GameResult::OWins => "1-0",
+        GameResult::XWins => "0-1",
+        GameResult::Draw => "1/2-1/2",
+    }
+}
+
+/// Parses a `[Name "Value"]` header line.
+fn parse_header(line: &str) -> Option<(String, String)> {
+    let line = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = line.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            '"' => return None,
+            c => unescaped.push(c),
+        }
+    }
+
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.to_string(), unescaped))
+}
+
+/// Returns true for move numbers, such as `12.` or `12...`.
+fn is_move_number(token: &str) -> bool {
+    let digits = token.trim_end_matches('.');
+    digits.len() < token.len() && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Returns the coordinate, such as `e5`, of the square on the full 9x9 grid.
+pub fn to_coordinate(board_pos: usize, square_pos: usize) -> String {
+    let col = (board_pos % 3) * 3 + square_pos % 3;
+    let row = (board_pos / 3) * 3 + square_pos / 3;
+    format!("{}{}", (b'a' + col as u8) as char, row + 1)
+}
+
+/// Parses a coordinate, such as `e5`, returning the (board_pos, square_pos).
+pub fn from_coordinate(s: &str) -> Option<(usize, usize)> {
+    let mut chars = s.chars();
+    let col = chars.next()?;
+    let row = chars.next()?;
+    if chars.next().is_some() || !('a'..='i').contains(&col) || !('1'..='9').contains(&row) {
+        return None;
+    }
+
+    let col = col as usize - 'a' as usize;
+    let row = row as usize - '1' as usize;
+    Some(((row / 3) * 3 + col / 3, (row % 3) * 3 + col % 3))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::MyError;
+    use crate::error::RecordError;
+    use crate::record::*;
+    use crate::DrawnBoards;
+    use crate::PossibleMoves;
+    use crate::TieBreak;
+    use rand::seq::IteratorRandom;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn coordinates() {
+        assert_eq!(to_coordinate(0, 0), "a1");
+        assert_eq!(to_coordinate(4, 4), "e5");
+        assert_eq!(to_coordinate(8, 8), "i9");
+        assert_eq!(to_coordinate(2, 3), "g2");
+
+        for board_pos in 0..9 {
+            for square_pos in 0..9 {
+                let s = to_coordinate(board_pos, square_pos);
+                assert_eq!(from_coordinate(&s), Some((board_pos, square_pos)));
+            }
+        }
+
+        assert_eq!(from_coordinate("j1"), None);
+        assert_eq!(from_coordinate("a0"), None);
+        assert_eq!(from_coordinate("a10"), None);
+        assert_eq!(from_coordinate(""), None);
+    }
+
+    #[test]
+    fn write() {
+        let mut g = Game::default();
+        g.play(4, 4).unwrap();
+        g.play(4, 1).unwrap();
+        g.play(1, 4).unwrap();
+
+        let mut record = GameRecord::from_game(&g);
+        record.set_header("Date", "2023.01.31");
+        record.set_header("X", "MonteCarloAI");
+        record.set_header("O", "Human \"Bob\"");
+
+        assert_eq!(
+            record.to_string(),
+            "[O \"Human \\\"Bob\\\"\"]\n\
+             [X \"MonteCarloAI\"]\n\
+             [Date \"2023.01.31\"]\n\
+             [Result \"*\"]\n\
+             \n\
+             1. e5 e4 2. e2 *\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for _ in 0..100 {
+            let mut g = Game::default();
+            while g.playable() {
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+                g.play(board_pos, square_pos).unwrap();
+            }
+
+            let mut record = GameRecord::from_game(&g);
+            record.set_header("O", "RandomAI");
+            record.set_header("X", "RandomAI");
+            record.set_header("Settings", "seed=0");
+
+            let parsed = GameRecord::parse(&record.to_string()).unwrap();
+            assert_eq!(parsed.header("Settings"), Some("seed=0"));
+            assert_eq!(parsed.moves(), record.moves());
+            assert_eq!(parsed.replay(), Ok(g));
+        }
+    }
+
+    #[test]
+    fn variant_round_trip() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+        let rules = Rules::new(true, DrawnBoards::Both, TieBreak::MostBoards);
+
+        for _ in 0..100 {
+            let mut g = Game::with_rules(rules);
+            while g.playable() {
+                let (board_pos, square_pos) = g.legal_moves().choose(r).unwrap();
+                g.play(board_pos, square_pos).unwrap();
+            }
+
+            let record = GameRecord::from_game(&g);
+            assert_eq!(record.header("Rules"), Some("wdt"));
+
+            let parsed = GameRecord::parse(&record.to_string()).unwrap();
+            assert_eq!(parsed.rules(), Ok(rules));
+            assert_eq!(parsed.replay(), Ok(g));
+        }
+
+        // Playing on a won board is only legal with the rules that allow it.
+        let moves = "1. a2 a4 2. b2 d4 3. c2 g4 4. a1";
+        assert!(GameRecord::parse(moves).is_err());
+        let record = GameRecord::parse(&format!("[Rules \"w\"]\n\n{} *", moves)).unwrap();
+        assert!(record.replay().unwrap().rules().play_won_boards);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            GameRecord::parse("[O Human]\n\n1. e5 *"),
+            Err(RecordError::InvalidHeader(1))
+        );
+        assert_eq!(
+            GameRecord::parse("1. e5 z9 *"),
+            Err(RecordError::InvalidMove("z9".to_string()))
+        );
+        assert_eq!(
+            GameRecord::parse("1. e5 * e4"),
+            Err(RecordError::InvalidMove("e4".to_string()))
+        );
+        assert_eq!(
+            GameRecord::parse("1. e5 e4 2. e2 e1 *"),
+            Err(RecordError::IllegalMove {
+                index: 3,
+                error: MyError::WrongBoard
+            })
+        );
+        assert_eq!(
+            GameRecord::parse("1. e5 e4 2. e2 e5 *"),
+            Err(RecordError::IllegalMove {
+                index: 3,
+                error: MyError::AlreadyPlayed
+            })
+        );
+        assert_eq!(
+            GameRecord::parse("1. e5 e4 2. e2 1-0"),
+            Err(RecordError::WrongResult("1-0".to_string()))
+        );
+        assert_eq!(
+            GameRecord::parse("[Result \"0-1\"]\n\n1. e5 e4 2. e2 *"),
+            Err(RecordError::WrongResult("0-1".to_string()))
+        );
+        assert_eq!(
+            GameRecord::parse("[Rules \"x\"]\n\n1. e5 *"),
+            Err(RecordError::InvalidRules("x".to_string()))
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use crate::error::MyError;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::str::FromStr;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// How drawn boards count towards winning the game.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Default, Eq, Hash, PartialEq, Copy, Clone)]
+pub enum DrawnBoards {
+    /// Drawn boards count for neither player.
+    #[default]
+    Neither,
+
+    /// Drawn boards count as won by both players.
+    Both,
+}
+
+/// How the game is decided when all the boards are over without a line.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Default, Eq, Hash, PartialEq, Copy, Clone)]
+pub enum TieBreak {
+    /// The game is a draw.
+    #[default]
+    Draw,
+
+    /// The player who won the most boards wins the game.
+    MostBoards,
+}
+
+/// The variant of the rules being played. The default is the most common
+/// rules, where being sent to a won or full board lets the player play
+/// anywhere, drawn boards count for neither player, and a game without a line
+/// of boards is a draw.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Default, Eq, Hash, PartialEq, Copy, Clone)]
+pub struct Rules {
+    /// Won boards can still be played on until they are full, so only being
+    /// sent to a full board lets the player play anywhere.
+    pub play_won_boards: bool,
+
+    pub drawn_boards: DrawnBoards,
+
+    pub tie_break: TieBreak,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Rules {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(play_won_boards: bool, drawn_boards: DrawnBoards, tie_break: TieBreak) -> Rules {
+        Rules {
+            play_won_boards,
+            drawn_boards,
+            tie_break,
+        }
+    }
+}
+
+/// Writes the rules as a flag for each variant that differs from the default:
+/// `w` if won boards can be played on, `d` if drawn boards count for both
+/// players, and `t` if ties are broken by the most boards. The default rules
+/// are written as `-`.
+impl Display for Rules {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        if *self == Rules::default() {
+            return write!(f, "-");
+        }
+        if self.play_won_boards {
+            write!(f, "w")?;
+        }
+        if self.drawn_boards == DrawnBoards::Both {
+            write!(f, "d")?;
+        }
+        if self.tie_break == TieBreak::MostBoards {
+            write!(f, "t")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the rules as written by [Display].
+impl FromStr for Rules {
+    type Err = MyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules::default();
+        if s == "-" {
+            return Ok(rules);
+        }
+        if s.is_empty() {
+            return Err(MyError::InvalidRules);
+        }
+
+        for c in s.chars() {
+            match c {
+                'w' if !rules.play_won_boards => rules.play_won_boards = true,
+                'd' if rules.drawn_boards == DrawnBoards::Neither => {
+                    rules.drawn_boards = DrawnBoards::Both
+                }
+                't' if rules.tie_break == TieBreak::Draw => rules.tie_break = TieBreak::MostBoards,
+                _ => return Err(MyError::InvalidRules),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::MyError;
+    use crate::rules::*;
+    use crate::Game;
+    use crate::MegaBoard;
+    use crate::PossibleMoves;
+    use crate::Square;
+    use crate::Winner;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// All the combinations of rules.
+    fn all_rules() -> Vec<Rules> {
+        let mut rules = Vec::new();
+        for play_won_boards in [false, true] {
+            for drawn_boards in [DrawnBoards::Neither, DrawnBoards::Both] {
+                for tie_break in [TieBreak::Draw, TieBreak::MostBoards] {
+                    rules.push(Rules::new(play_won_boards, drawn_boards, tie_break));
+                }
+            }
+        }
+        rules
+    }
+
+    /// Plays the squares on the board, alternating between O and X.
+    fn play_board(b: &mut MegaBoard, board_pos: usize, squares: &[usize]) {
+        for (i, &square_pos) in squares.iter().enumerate() {
+            let player = if i % 2 == 0 { Square::O } else { Square::X };
+            b.play(board_pos, square_pos, player).unwrap();
+        }
+    }
+
+    // O wins with the top row.
+    const O_WINS: [usize; 5] = [0, 3, 1, 4, 2];
+
+    // X wins with the top row.
+    const X_WINS: [usize; 6] = [3, 0, 4, 1, 8, 2];
+
+    // ┌───────────┐
+    // │ O │ X │ O │
+    // │───┼───┼───│
+    // │ O │ X │ X │
+    // │───┼───┼───│
+    // │ X │ O │ O │
+    // └───────────┘
+    const DRAW: [usize; 9] = [0, 1, 2, 4, 3, 5, 7, 6, 8];
+
+    #[test]
+    fn play_won_boards() {
+        let moves = [
+            (0, 3),
+            (3, 0),
+            (0, 4),
+            (4, 0),
+            (0, 5), // O wins board 0
+            (5, 0), // X sends O back to board 0
+        ];
+
+        let mut g = Game::default();
+        for (board_pos, square_pos) in moves {
+            g.play(board_pos, square_pos).unwrap();
+        }
+        assert_eq!(g[0].winner(), Square::O);
+        assert_eq!(g.current_board(), None);
+        assert_eq!(g.play(0, 0), Err(MyError::AlreadyWon));
+
+        let rules = Rules {
+            play_won_boards: true,
+            ..Default::default()
+        };
+        let mut g = Game::with_rules(rules);
+        for (board_pos, square_pos) in moves {
+            g.play(board_pos, square_pos).unwrap();
+        }
+        assert_eq!(g[0].winner(), Square::O);
+        assert_eq!(g.current_board(), Some(0));
+        assert!(!g.is_legal(1, 0));
+        g.play(0, 0).unwrap();
+        assert_eq!(g[0].winner(), Square::O);
+    }
+
+    #[test]
+    fn drawn_boards() {
+        for (drawn_boards, winner) in [
+            (DrawnBoards::Neither, Square::None),
+            (DrawnBoards::Both, Square::O),
+        ] {
+            let rules = Rules {
+                drawn_boards,
+                ..Default::default()
+            };
+            let b = &mut MegaBoard::with_rules(rules);
+
+            play_board(b, 0, &O_WINS);
+            play_board(b, 1, &O_WINS);
+            play_board(b, 2, &DRAW);
+            assert_eq!(b.winner(), winner);
+        }
+    }
+
+    #[test]
+    fn drawn_boards_complete_both() {
+        let rules = Rules {
+            drawn_boards: DrawnBoards::Both,
+            ..Default::default()
+        };
+        let b = &mut MegaBoard::with_rules(rules);
+
+        play_board(b, 0, &O_WINS);
+        play_board(b, 1, &O_WINS);
+        play_board(b, 4, &X_WINS);
+        play_board(b, 6, &X_WINS);
+
+        // The draw completes a line for both, so the player who finished it wins.
+        play_board(b, 2, &DRAW[..8]);
+        b.play(2, DRAW[8], Square::X).unwrap();
+        assert_eq!(b.winner(), Square::X);
+    }
+
+    #[test]
+    fn tie_break() {
+        // ┌───────────┐
+        // │ O │ O │ X │
+        // │───┼───┼───│
+        // │ X │ X │ O │
+        // │───┼───┼───│
+        // │ O │ X │ O │
+        // └───────────┘
+        for (tie_break, winner) in [
+            (TieBreak::Draw, Square::None),
+            (TieBreak::MostBoards, Square::O),
+        ] {
+            let rules = Rules {
+                tie_break,
+                ..Default::default()
+            };
+            let b = &mut MegaBoard::with_rules(rules);
+
+            for board_pos in [0, 1, 5, 6, 8] {
+                play_board(b, board_pos, &O_WINS);
+            }
+            for board_pos in [2, 3, 4, 7] {
+                play_board(b, board_pos, &X_WINS);
+            }
+
+            assert!(!b.playable());
+            assert_eq!(b.winner(), winner);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        for rules in all_rules() {
+            assert_eq!(rules.to_string().parse(), Ok(rules));
+        }
+        assert_eq!(Rules::default().to_string(), "-");
+        assert_eq!(
+            "wt".parse(),
+            Ok(Rules::new(true, DrawnBoards::Neither, TieBreak::MostBoards))
+        );
+
+        for invalid in ["", "ww", "x", "w-"] {
+            assert_eq!(invalid.parse::<Rules>(), Err(MyError::InvalidRules));
+        }
+    }
+
+    #[test]
+    fn undo_round_trip() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for rules in all_rules() {
+            for _ in 0..100 {
+                let mut g = Game::with_rules(rules);
+                let mut states = vec![g];
+
+                while g.playable() {
+                    let board_pos = g.choose(r).unwrap();
+                    let square_pos = g[board_pos].choose(r).unwrap();
+
+                    g.play(board_pos, square_pos).unwrap();
+                    states.push(g);
+                }
+
+                for expected in states.iter().rev().skip(1) {
+                    g.undo().unwrap();
+                    assert_eq!(&g, expected);
+                }
+            }
+        }
+    }
+}
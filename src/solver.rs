@@ -0,0 +1,375 @@
+//! Exact solving of positions near the end of the game.
+//!
+//! The solver searches every move to the end of the game, only caring if the
+//! game is won, lost or drawn, so it proves the value of the position rather
+//! than estimating it. This is only practical once few squares are left.
+use crate::alphabeta::ordered_moves;
+use crate::bitboard;
+use crate::transposition::Bound;
+use crate::transposition::SearchEntry;
+use crate::transposition::TranspositionTable;
+use crate::Game;
+use crate::PossibleMoves;
+use crate::Square;
+use crate::Winner;
+
+/// The outcome of a game with best play, for the player to move.
+#[derive(Debug, Eq, Hash, PartialEq, Copy, Clone)]
+pub enum Value {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Value {
+    fn from_score(score: i32) -> Value {
+        match score {
+            1 => Value::Win,
+            0 => Value::Draw,
+            _ => Value::Loss,
+        }
+    }
+}
+
+/// The default size of the solver's transposition table, in bytes. Smaller
+/// than for a full search, as the solver is only used near the end of the game.
+pub const DEFAULT_TABLE_SIZE: usize = 2 * 1024 * 1024;
+
+/// A solved position.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Solution {
+    pub value: Value,
+
+    /// A sequence of best moves for both players, usually to the end of the
+    /// game. It may stop early if the node limit is reached while following
+    /// it, but always has at least the first move, unless the game is over.
+    pub pv: Vec<(usize, usize)>,
+}
+
+/// Proves the value of positions, giving up after searching a number of nodes.
+pub struct Solver {
+    node_limit: usize,
+    nodes: usize,
+    table: TranspositionTable<SearchEntry>,
+}
+
+impl Solver {
+    pub fn new(node_limit: usize) -> Self {
+        Self::with_table_size(node_limit, DEFAULT_TABLE_SIZE)
+    }
+
+    /// Creates a solver with a transposition table of `size` bytes.
+    pub fn with_table_size(node_limit: usize, size: usize) -> Self {
+        Solver {
+            node_limit,
+            nodes: 0,
+            table: TranspositionTable::new(size),
+        }
+    }
+
+    pub fn node_limit(&self) -> usize {
+        self.node_limit
+    }
+
+    /// Returns the number of nodes searched by the last call to solve.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Returns the value of the game with the best moves to achieve it, or
+    /// None if it couldn't be proved within the node limit.
+    pub fn solve(&mut self, game: &Game) -> Option<Solution> {
+        self.nodes = 0;
+        self.table.new_search();
+
+        let mut g = *game;
+        let score = self.negamax(&mut g, -1, 1)?;
+
+        // A finished game has a value, but no moves to achieve it.
+        if !g.playable() {
+            return Some(Solution {
+                value: Value::from_score(score),
+                pv: Vec::new(),
+            });
+        }
+
+        // The root has the most open squares, so its entry is never replaced,
+        // and its best move achieves the value.
+        let first = self.best_move(&g).expect("root entry in the table");
+
+        // Following the moves gets its own node limit, so a proved value is
+        // never lost, at worst the principal variation is cut short.
+        let proof_nodes = self.nodes;
+        self.nodes = 0;
+        let mut pv = self.pv(&mut g, score);
+        if pv.is_empty() {
+            pv.push(first);
+        }
+        self.nodes += proof_nodes;
+
+        Some(Solution {
+            value: Value::from_score(score),
+            pv,
+        })
+    }
+
+    /// Follows the moves that keep the value, which are mostly in the table,
+    /// until the end of the game or the node limit.
+    fn pv(&mut self, g: &mut Game, mut expected: i32) -> Vec<(usize, usize)> {
+        let mut pv = Vec::new();
+        while g.playable() {
+            let mut found = None;
+            for (board_pos, square_pos) in ordered_moves(g, self.best_move(g)) {
+                g.play(board_pos, square_pos).expect("legal move");
+                let score = self.negamax(g, -1, 1);
+                g.undo().expect("undo legal move");
+
+                match score {
+                    Some(score) if -score == expected => {
+                        found = Some((board_pos, square_pos));
+                        break;
+                    }
+                    Some(_) => {}
+                    None => return pv,
+                }
+            }
+
+            let (board_pos, square_pos) = found.expect("a move that keeps the value");
+            g.play(board_pos, square_pos).expect("legal move");
+            pv.push((board_pos, square_pos));
+            expected = -expected;
+        }
+        pv
+    }
+
+    fn best_move(&mut self, g: &Game) -> Option<(usize, usize)> {
+        let (_, entry) = self.table.probe(g.hash())?;
+        entry.best.map(|(b, s)| (b as usize, s as usize))
+    }
+
+    /// Returns 1, 0 or -1 if the player to move wins, draws or loses, or None
+    /// if the node limit was reached.
+    fn negamax(&mut self, g: &mut Game, mut alpha: i32, mut beta: i32) -> Option<i32> {
+        if !g.playable() {
+            let winner = g.winner();
+            return Some(if winner == Square::None {
+                0
+            } else if winner == g.current_player() {
+                1
+            } else {
+                -1
+            });
+        }
+
+        self.nodes += 1;
+        if self.nodes > self.node_limit {
+            return None;
+        }
+
+        let original_alpha = alpha;
+        let mut first = None;
+        if let Some((_, entry)) = self.table.probe(g.hash()) {
+            first = entry.best.map(|(b, s)| (b as usize, s as usize));
+
+            match entry.bound {
+                Bound::Exact => return Some(entry.score),
+                Bound::Lower => alpha = alpha.max(entry.score),
+                Bound::Upper => beta = beta.min(entry.score),
+            }
+            if alpha >= beta {
+                return Some(entry.score);
+            }
+        }
+
+        let mut best = -1;
+        let mut best_move = None;
+        for (board_pos, square_pos) in ordered_moves(g, first) {
+            g.play(board_pos, square_pos).expect("legal move");
+            let score = self.negamax(g, -beta, -alpha);
+            g.undo().expect("undo legal move");
+
+            let score = -score?;
+            if best_move.is_none() || score > best {
+                best = score;
+                best_move = Some((board_pos as u8, square_pos as u8));
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+
+        // Bigger subtrees are more valuable to keep.
+        let depth = g.open_squares().min(u8::MAX as usize) as u8;
+        self.table.store(
+            g.hash(),
+            depth,
+            SearchEntry {
+                bound,
+                score: best,
+                best: best_move,
+            },
+        );
+
+        Some(best)
+    }
+}
+
+impl Game {
+    /// Returns the number of empty squares on boards that can still be played.
+    pub fn open_squares(&self) -> usize {
+        bitboard::positions(self.megaboard().open())
+            .map(|board_pos| self[board_pos].empty().count_ones() as usize)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::ai::AI;
+    use crate::alphabeta::AlphaBetaAI;
+    use crate::alphabeta::WIN;
+    use crate::solver::*;
+    use crate::Game;
+    use crate::PossibleMoves;
+    use crate::Square;
+    use crate::Winner;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn winning_move() {
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OO.XX..../..X....../X......../........./........./........./......... O 2 16",
+        )
+        .unwrap();
+
+        let solution = Solver::new(1_000_000).solve(&g).unwrap();
+        assert_eq!(solution.value, Value::Win);
+        assert_eq!(solution.pv, vec![(2, 2)]);
+    }
+
+    #[test]
+    fn game_over() {
+        // O has already won, so X to move has lost.
+        let g = Game::from_notation(
+            "OOOXX..../OOOXX..../OOOXX..../..X....../X......../........./........./........./......... X - 17",
+        )
+        .unwrap();
+
+        let solution = Solver::new(1000).solve(&g).unwrap();
+        assert_eq!(solution.value, Value::Loss);
+        assert!(solution.pv.is_empty());
+
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut g = Game::default();
+            while g.playable() {
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+                g.play(board_pos, square_pos).unwrap();
+            }
+
+            let expected = match g.winner() {
+                Square::None => Value::Draw,
+                winner if winner == g.current_player() => Value::Win,
+                _ => Value::Loss,
+            };
+            let solution = Solver::new(1000).solve(&g).unwrap();
+            assert_eq!(solution.value, expected);
+            assert!(solution.pv.is_empty());
+        }
+    }
+
+    #[test]
+    fn matches_alphabeta() {
+        for notation in [
+            ".XXXOOOOX/OX.O.OO.X/O.OX...OO/XX.O.OOX./OOX....../X...X.O.X/XOXX...XX/..XX.XXOO/OX.OXO.O. O 3 50",
+            "XO..OOO../XXXO.XXX./XOXOOO.XX/.OX..X..X/.OXOXOO.X/XOOX.XO.O/XX..X.O.X/.O..X.O../O.....OOO O 0 50",
+        ] {
+            let g = Game::from_notation(notation).unwrap();
+
+            let mut solver = Solver::new(10_000_000);
+            let solution = solver.solve(&g).unwrap();
+
+            // A search to the end of the game gives the same value.
+            let mut ai = AlphaBetaAI::with_budget(Budget::Nodes(usize::MAX));
+            ai.choose(ChaCha8Rng::seed_from_u64(0), &g).unwrap();
+            let expected = match ai.last_results.score {
+                score if score > WIN / 2 => Value::Win,
+                score if score < -WIN / 2 => Value::Loss,
+                _ => Value::Draw,
+            };
+            assert_eq!(solution.value, expected, "{}", notation);
+
+            // Playing the principal variation gives the same result.
+            let mut end = g;
+            for (board_pos, square_pos) in &solution.pv {
+                end.play(*board_pos, *square_pos).unwrap();
+            }
+            assert!(!end.playable());
+            let winner = end.winner();
+            let value = if winner == Square::None {
+                Value::Draw
+            } else if winner == g.current_player() {
+                Value::Win
+            } else {
+                Value::Loss
+            };
+            assert_eq!(value, solution.value);
+        }
+    }
+
+    #[test]
+    fn proved_with_node_limit() {
+        let g = Game::from_notation(
+            ".XXXOOOOX/OX.O.OO.X/O.OX...OO/XX.O.OOX./OOX....../X...X.O.X/XOXX...XX/..XX.XXOO/OX.OXO.O. O 3 50",
+        )
+        .unwrap();
+        let expected = Solver::new(10_000_000).solve(&g).unwrap().value;
+
+        // Find the fewest nodes needed to prove the value.
+        let (mut low, mut high) = (0, 10_000_000);
+        while low < high {
+            let mid = (low + high) / 2;
+            match Solver::new(mid).solve(&g) {
+                Some(_) => high = mid,
+                None => low = mid + 1,
+            }
+        }
+
+        // Following the principal variation takes more nodes, but doesn't
+        // lose the proved value.
+        let mut solver = Solver::new(low);
+        let solution = solver.solve(&g).unwrap();
+        assert_eq!(solution.value, expected);
+        assert!(!solution.pv.is_empty());
+        assert!(solver.nodes() > low);
+
+        let mut end = g;
+        for (board_pos, square_pos) in &solution.pv {
+            end.play(*board_pos, *square_pos).unwrap();
+        }
+    }
+
+    #[test]
+    fn node_limit() {
+        let g = Game::default();
+        let mut solver = Solver::new(1000);
+        assert_eq!(solver.solve(&g), None);
+        assert_eq!(solver.nodes(), 1001);
+
+        assert_eq!(g.open_squares(), 81);
+    }
+}
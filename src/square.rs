@@ -4,16 +4,22 @@ use core::fmt::Formatter;
 use wasm_bindgen::prelude::*;
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Debug, Eq, Hash, PartialEq, Copy, Clone)]
+#[derive(Debug, Default, Eq, Hash, PartialEq, Copy, Clone)]
 pub enum Square {
+    #[default]
     None,
     O,
     X,
 }
 
-impl Default for Square {
-    fn default() -> Self {
-        Square::None
+impl Square {
+    /// Returns the other player, or None for None.
+    pub fn opponent(self) -> Square {
+        match self {
+            Self::None => Self::None,
+            Self::O => Self::X,
+            Self::X => Self::O,
+        }
     }
 }
 
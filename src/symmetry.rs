@@ -0,0 +1,192 @@
+//! The 8 symmetries (rotations and reflections) of the grid.
+//!
+//! A symmetry is applied to both levels at once, mapping each `board_pos` and
+//! each `square_pos` with the same transform, so equivalent positions can be
+//! treated as one.
+use crate::Game;
+
+/// One of the 8 rotations and reflections of a 3x3 grid.
+#[derive(Debug, Eq, Hash, PartialEq, Copy, Clone)]
+pub enum Symmetry {
+    Identity,
+    /// Rotate 90° clockwise.
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Mirror left to right.
+    FlipHorizontal,
+    /// Mirror top to bottom.
+    FlipVertical,
+    /// Mirror along the diagonal from top left to bottom right.
+    FlipDiagonal,
+    /// Mirror along the diagonal from top right to bottom left.
+    FlipAntiDiagonal,
+}
+
+/// Where each position moves to, for each symmetry.
+const TABLE: [[usize; 9]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8],
+    [2, 5, 8, 1, 4, 7, 0, 3, 6],
+    [8, 7, 6, 5, 4, 3, 2, 1, 0],
+    [6, 3, 0, 7, 4, 1, 8, 5, 2],
+    [2, 1, 0, 5, 4, 3, 8, 7, 6],
+    [6, 7, 8, 3, 4, 5, 0, 1, 2],
+    [0, 3, 6, 1, 4, 7, 2, 5, 8],
+    [8, 5, 2, 7, 4, 1, 6, 3, 0],
+];
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::FlipHorizontal,
+        Symmetry::FlipVertical,
+        Symmetry::FlipDiagonal,
+        Symmetry::FlipAntiDiagonal,
+    ];
+
+    /// Returns where the position moves to under this symmetry.
+    #[inline]
+    pub fn apply(self, pos: usize) -> usize {
+        TABLE[self as usize][pos]
+    }
+
+    /// Returns the bitmask with each position moved.
+    pub fn apply_mask(self, mask: u16) -> u16 {
+        let mut result = 0;
+        for (pos, &to) in TABLE[self as usize].iter().enumerate() {
+            if mask & (1 << pos) != 0 {
+                result |= 1 << to;
+            }
+        }
+        result
+    }
+
+    /// Returns the symmetry that undoes this one.
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            s => s,
+        }
+    }
+}
+
+impl Game {
+    /// Returns the hash of the canonical form of this position, which is the
+    /// same for all symmetric positions.
+    pub fn canonical_hash(&self) -> u64 {
+        self.canonical().0.hash()
+    }
+
+    /// Returns the canonical form of this position, and the symmetry that maps
+    /// this position to it.
+    pub fn canonical(&self) -> (Game, Symmetry) {
+        Symmetry::ALL
+            .iter()
+            .map(|&s| (self.transform(s), s))
+            .min_by_key(|(g, _)| g.hash())
+            .unwrap()
+    }
+
+    /// Returns the symmetries, other than the identity, that leave this
+    /// position unchanged.
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        Symmetry::ALL[1..]
+            .iter()
+            .copied()
+            .filter(|&s| self.transform(s).same_position(self))
+            .collect()
+    }
+
+    /// Returns the legal moves, skipping any move that is symmetric to an
+    /// earlier one in this position. From the empty board this is 15 of the 81
+    /// moves.
+    pub fn distinct_moves(&self) -> Vec<(usize, usize)> {
+        let symmetries = self.symmetries();
+
+        let index = |(board_pos, square_pos): (usize, usize)| board_pos * 9 + square_pos;
+
+        self.legal_moves()
+            .filter(|&m| {
+                symmetries
+                    .iter()
+                    .all(|s| index((s.apply(m.0), s.apply(m.1))) >= index(m))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symmetry::Symmetry;
+    use crate::Game;
+    use crate::PossibleMoves;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn inverse() {
+        for s in Symmetry::ALL {
+            for pos in 0..9 {
+                assert_eq!(s.inverse().apply(s.apply(pos)), pos);
+            }
+            assert_eq!(s.apply_mask(0b111_111_111), 0b111_111_111);
+            assert_eq!(s.apply(4), 4);
+        }
+    }
+
+    #[test]
+    fn distinct_moves() {
+        let mut g = Game::default();
+        assert_eq!(g.distinct_moves().len(), 15);
+
+        // After the centre, only the centre board's symmetries remain.
+        g.play(4, 4).unwrap();
+        assert_eq!(g.distinct_moves(), vec![(4, 0), (4, 1)]);
+
+        g.play(4, 1).unwrap();
+        assert_eq!(
+            g.distinct_moves(),
+            vec![(1, 0), (1, 1), (1, 3), (1, 4), (1, 6), (1, 7)]
+        );
+    }
+
+    #[test]
+    fn transform() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        for _ in 0..20 {
+            let mut g = Game::default();
+
+            while g.playable() {
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+
+                let canonical = g.canonical_hash();
+                for s in Symmetry::ALL {
+                    let mut t = g.transform(s);
+                    assert_eq!(t.canonical_hash(), canonical);
+                    assert_eq!(t.transform(s.inverse()), g);
+
+                    let mut moves: Vec<_> = g
+                        .legal_moves()
+                        .map(|(b, sq)| (s.apply(b), s.apply(sq)))
+                        .collect();
+                    moves.sort();
+                    assert_eq!(t.legal_moves().collect::<Vec<_>>(), moves);
+
+                    // Playing the transformed move keeps the games symmetric.
+                    let mut played = g;
+                    played.play(board_pos, square_pos).unwrap();
+                    t.play(s.apply(board_pos), s.apply(square_pos)).unwrap();
+                    assert_eq!(t, played.transform(s));
+                }
+
+                g.play(board_pos, square_pos).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! A fixed-size cache of search results, keyed by the [Game::hash] of the
+//! position, so positions reached by different move orders are only searched
+//! once.
+//!
+//! [Game::hash]: crate::Game::hash
+use core::mem::size_of;
+
+/// The default size of a table, in bytes.
+pub const DEFAULT_SIZE: usize = 16 * 1024 * 1024;
+
+/// How the stored score relates to the true score of the position.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub enum Bound {
+    /// The score is exact.
+    #[default]
+    Exact,
+
+    /// The true score is at least the score, as the search was cut off.
+    Lower,
+
+    /// The true score is at most the score, as no move beat alpha.
+    Upper,
+}
+
+/// The result of searching a position with alpha-beta.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct SearchEntry {
+    pub bound: Bound,
+    pub score: i32,
+
+    /// The best (board_pos, square_pos) found.
+    pub best: Option<(u8, u8)>,
+}
+
+/// Counts of how the table was used since the last [TranspositionTable::new_search].
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct TableStats {
+    /// Number of lookups.
+    pub probes: usize,
+
+    /// Lookups that found the position.
+    pub hits: usize,
+
+    /// Lookups that found a different position in the position's slot.
+    pub collisions: usize,
+
+    /// Number of entries written.
+    pub stores: usize,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / self.probes as f64
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Slot<T> {
+    key: u64,
+
+    /// How much work went into the value, so the deepest results are kept.
+    depth: u8,
+
+    /// The search that last used this slot. Slots from older searches are
+    /// always replaced.
+    generation: u8,
+
+    used: bool,
+    value: T,
+}
+
+/// A table with one entry per slot, where a new entry replaces the old one if
+/// it is from a deeper search, or the old entry is from an earlier search.
+pub struct TranspositionTable<T> {
+    slots: Vec<Slot<T>>,
+    generation: u8,
+    stats: TableStats,
+}
+
+impl<T: Default + Copy> TranspositionTable<T> {
+    /// Creates a table using at most `size` bytes. The number of slots is
+    /// rounded down to a power of two.
+    pub fn new(size: usize) -> Self {
+        let slots = (size / size_of::<Slot<T>>()).max(1);
+        let slots = 1 << (usize::BITS - 1 - slots.leading_zeros());
+
+        TranspositionTable {
+            slots: vec![Slot::default(); slots],
+            generation: 0,
+            stats: TableStats::default(),
+        }
+    }
+
+    /// Returns the number of entries the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.slots.len() - 1)
+    }
+
+    /// Starts a new search, so entries from earlier searches can be replaced,
+    /// and resets the stats.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.stats = TableStats::default();
+    }
+
+    /// Removes every entry.
+    pub fn clear(&mut self) {
+        self.slots.fill(Slot::default());
+        self.stats = TableStats::default();
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    /// Returns the depth and value stored for the position.
+    pub fn probe(&mut self, key: u64) -> Option<(u8, T)> {
+        self.stats.probes += 1;
+
+        let index = self.index(key);
+        let slot = &mut self.slots[index];
+        if !slot.used {
+            return None;
+        }
+        if slot.key != key {
+            self.stats.collisions += 1;
+            return None;
+        }
+
+        self.stats.hits += 1;
+        slot.generation = self.generation;
+        Some((slot.depth, slot.value))
+    }
+
+    /// Stores the value for the position, unless its slot holds a deeper
+    /// result for another position from this search.
+    pub fn store(&mut self, key: u64, depth: u8, value: T) {
+        let index = self.index(key);
+        let generation = self.generation;
+        let slot = &mut self.slots[index];
+
+        if slot.used && slot.key != key && slot.generation == generation && slot.depth > depth {
+            return;
+        }
+
+        self.stats.stores += 1;
+        *slot = Slot {
+            key,
+            depth,
+            generation,
+            used: true,
+            value,
+        };
+    }
+
+    /// Updates the value stored for the position in place, starting from the
+    /// default value if it isn't stored. Does nothing if the slot is kept for
+    /// another position.
+    pub fn update(&mut self, key: u64, depth: u8, f: impl FnOnce(&mut T)) {
+        if self.probe(key).is_some() {
+            let index = self.index(key);
+            let slot = &mut self.slots[index];
+            slot.depth = slot.depth.max(depth);
+            f(&mut slot.value);
+        } else {
+            let mut value = T::default();
+            f(&mut value);
+            self.store(key, depth, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transposition::*;
+
+    #[test]
+    fn size() {
+        let table = TranspositionTable::<SearchEntry>::new(1024 * 1024);
+        assert!(table.capacity().is_power_of_two());
+        assert!(table.capacity() * size_of::<Slot<SearchEntry>>() <= 1024 * 1024);
+
+        assert_eq!(TranspositionTable::<SearchEntry>::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn replace_by_depth() {
+        let mut table = TranspositionTable::<i32>::new(1024);
+        let capacity = table.capacity() as u64;
+
+        // Both keys use the same slot.
+        let (a, b) = (1, 1 + capacity);
+
+        table.store(a, 5, 1);
+        assert_eq!(table.probe(a), Some((5, 1)));
+
+        // A shallower result for another position doesn't replace it.
+        table.store(b, 4, 2);
+        assert_eq!(table.probe(b), None);
+        assert_eq!(table.probe(a), Some((5, 1)));
+
+        // Unless it is from an earlier search.
+        table.new_search();
+        table.store(b, 4, 2);
+        assert_eq!(table.probe(b), Some((4, 2)));
+        assert_eq!(table.probe(a), None);
+
+        // The same position is always replaced.
+        table.store(b, 1, 3);
+        assert_eq!(table.probe(b), Some((1, 3)));
+
+        let stats = table.stats();
+        assert_eq!(stats.probes, 3);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.collisions, 1);
+        assert_eq!(stats.stores, 2);
+        assert_eq!(stats.hit_rate(), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn update() {
+        let mut table = TranspositionTable::<usize>::new(1024);
+        table.update(7, 1, |v| *v += 1);
+        table.update(7, 1, |v| *v += 1);
+        assert_eq!(table.probe(7), Some((1, 2)));
+    }
+}
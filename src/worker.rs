@@ -0,0 +1,203 @@
+//! The entry point for analysing positions in a Web Worker, so the search
+//! doesn't block the page.
+//!
+//! Messages are plain objects, so they can be posted between threads. The
+//! worker receives a request:
+//!
+//! ```text
+//! {
+//!   id: 1,                 // Copied to the response.
+//!   game: "OX......./...", // The position, in the notation of crate::notation.
+//!   options: {             // All optional.
+//!     time_ms: 1000,       // Search for this long.
+//!     playouts: 10000,     // Or stop after this many playouts, if sooner.
+//!     seed: 42,            // Seeds the search, for reproducible results.
+//!     exploration: 1.4,    // The exploration constant for the tree search.
+//!     policy: 4,           // A Policy, for how moves are picked during playouts.
+//!   },
+//! }
+//! ```
+//!
+//! and responds with the results, or `{ id, error }` if the request is invalid:
+//!
+//! ```text
+//! {
+//!   id: 1,
+//!   best: { board_pos: 4, square_pos: 4 }, // Or null if the game is over.
+//!   runs: 10000,
+//!   stats: [ { wins, loses, totals }, ... ], // 81 entries, indexed by board_pos * 9 + square_pos.
+//! }
+//! ```
+use crate::ai::Budget;
+use crate::ai::AI;
+use crate::mcts::MctsAI;
+use crate::mcts::DEFAULT_EXPLORATION;
+use crate::monte_carlo::MegaBoardStats;
+use crate::playout::Policy;
+use crate::Game;
+use crate::PossibleMoves;
+use core::time::Duration;
+use js_sys::Array;
+use js_sys::Object;
+use js_sys::Reflect;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use wasm_bindgen::prelude::*;
+
+/// The search time used when a request doesn't give a budget.
+const DEFAULT_TIME_MS: f64 = 1000.0;
+
+/// The playout policies, indexed by their value in JavaScript.
+const POLICIES: [Policy; 5] = [
+    Policy::Uniform,
+    Policy::TakeWins,
+    Policy::BlockWins,
+    Policy::AvoidFreeMoves,
+    Policy::Greedy,
+];
+
+/// Handles a request posted to the worker, returning the response to post back.
+#[wasm_bindgen]
+pub fn analyze_message(request: JsValue) -> JsValue {
+    let id = get(&request, "id").unwrap_or(JsValue::NULL);
+
+    let response = match analyze(&request) {
+        Ok(response) => response,
+        Err(error) => {
+            let response = Object::new();
+            set(&response, "error", &JsValue::from_str(&error));
+            response
+        }
+    };
+    set(&response, "id", &id);
+    response.into()
+}
+
+fn analyze(request: &JsValue) -> Result<Object, String> {
+    let notation = get(request, "game")
+        .and_then(|game| game.as_string())
+        .ok_or("Missing game")?;
+    let game = Game::from_notation(&notation).map_err(|e| e.to_string())?;
+
+    let options = get(request, "options").unwrap_or(JsValue::UNDEFINED);
+    let number = |name| match get(&options, name) {
+        Some(v) => v.as_f64().map(Some).ok_or(format!("Invalid {}", name)),
+        None => Ok(None),
+    };
+    let budget = budget(number("time_ms")?, number("playouts")?);
+
+    let exploration = number("exploration")?.unwrap_or(DEFAULT_EXPLORATION);
+    if !exploration.is_finite() || exploration < 0.0 {
+        return Err("Invalid exploration".to_string());
+    }
+    let policy = match number("policy")? {
+        Some(value) => policy(value).ok_or("Invalid policy")?,
+        None => Policy::default(),
+    };
+
+    let mut rng = match number("seed")? {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed as u64),
+        None => ChaCha8Rng::from_rng(rand::thread_rng()).unwrap(),
+    };
+
+    let response = Object::new();
+    let mut results = MegaBoardStats::default();
+    let mut best = JsValue::NULL;
+    if game.playable() {
+        let mut ai = MctsAI::with_budget(budget)
+            .with_exploration(exploration)
+            .with_policy(policy);
+        let m = ai.choose(&mut rng, &game).map_err(|e| e.to_string())?;
+
+        let pos = Object::new();
+        set(&pos, "board_pos", &JsValue::from(m.board_pos as u32));
+        set(&pos, "square_pos", &JsValue::from(m.square_pos as u32));
+        best = pos.into();
+        results = ai.last_results;
+    }
+
+    let stats = Array::new();
+    for stat in results.board.iter().flatten() {
+        let s = Object::new();
+        set(&s, "wins", &JsValue::from(stat.wins as u32));
+        set(&s, "loses", &JsValue::from(stat.loses as u32));
+        set(&s, "totals", &JsValue::from(stat.totals as u32));
+        stats.push(&s);
+    }
+
+    set(&response, "best", &best);
+    set(&response, "runs", &JsValue::from(results.runs as u32));
+    set(&response, "stats", &stats);
+    Ok(response)
+}
+
+/// Returns the budget for the requested time and playouts.
+fn budget(time_ms: Option<f64>, playouts: Option<f64>) -> Budget {
+    let time = |ms: f64| Duration::from_secs_f64(ms.max(0.0) / 1000.0);
+    match (time_ms, playouts) {
+        (Some(ms), Some(playouts)) => Budget::First {
+            time: time(ms),
+            playouts: playouts as usize,
+            nodes: usize::MAX,
+        },
+        (None, Some(playouts)) => Budget::Playouts(playouts as usize),
+        (ms, None) => Budget::Time(time(ms.unwrap_or(DEFAULT_TIME_MS))),
+    }
+}
+
+/// Returns the playout policy for the value of a `Policy` from JavaScript.
+fn policy(value: f64) -> Option<Policy> {
+    if value.fract() != 0.0 || value < 0.0 {
+        return None;
+    }
+    POLICIES.get(value as usize).copied()
+}
+
+fn get(object: &JsValue, key: &str) -> Option<JsValue> {
+    if !object.is_object() {
+        return None;
+    }
+    Reflect::get(object, &JsValue::from_str(key))
+        .ok()
+        .filter(|v| !v.is_undefined())
+}
+
+fn set(object: &Object, key: &str, value: &JsValue) {
+    Reflect::set(object, &JsValue::from_str(key), value).expect("set property on object");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Budget;
+    use crate::playout::Policy;
+    use crate::worker::budget;
+    use crate::worker::policy;
+    use std::time::Duration;
+
+    #[test]
+    fn budgets() {
+        assert_eq!(budget(None, None), Budget::Time(Duration::from_secs(1)));
+        assert_eq!(
+            budget(Some(250.0), None),
+            Budget::Time(Duration::from_millis(250))
+        );
+        assert_eq!(budget(None, Some(500.0)), Budget::Playouts(500));
+        assert_eq!(
+            budget(Some(250.0), Some(500.0)),
+            Budget::First {
+                time: Duration::from_millis(250),
+                playouts: 500,
+                nodes: usize::MAX,
+            }
+        );
+    }
+
+    #[test]
+    fn policies() {
+        assert_eq!(policy(0.0), Some(Policy::Uniform));
+        assert_eq!(policy(Policy::Greedy as u32 as f64), Some(Policy::Greedy));
+        assert_eq!(policy(5.0), None);
+        assert_eq!(policy(1.5), None);
+        assert_eq!(policy(-1.0), None);
+    }
+}
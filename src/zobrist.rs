@@ -0,0 +1,135 @@
+//! Zobrist hashing of [Game] positions.
+//!
+//! Each feature of a position (a player on a square, the player to move, and
+//! the forced board) has a random key, and the hash of a position is the xor of
+//! the keys of its features. This allows [Game::play] to update the hash
+//! incrementally as features are added and removed.
+use crate::Game;
+use crate::Square;
+
+/// Random keys, laid out as 81 squares for O, 81 squares for X, X to move, and the 9 forced boards.
+const KEYS: [u64; 81 * 2 + 1 + 9] = {
+    let mut keys = [0; 81 * 2 + 1 + 9];
+
+    // Fixed seed, so hashes are the same between builds.
+    let mut state = 0x5555_5555_5555_5555;
+
+    let mut i = 0;
+    while i < keys.len() {
+        state = splitmix64(state);
+        keys[i] = state;
+        i += 1;
+    }
+    keys
+};
+
+const X_OFFSET: usize = 81;
+const X_TO_MOVE: usize = 81 * 2;
+const FORCED_BOARD_OFFSET: usize = X_TO_MOVE + 1;
+
+/// Steps the splitmix64 generator, which is simple enough to run at compile time.
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Returns the key for the player on the square.
+#[inline]
+pub fn square(board_pos: usize, square_pos: usize, player: Square) -> u64 {
+    match player {
+        Square::O => KEYS[board_pos * 9 + square_pos],
+        Square::X => KEYS[X_OFFSET + board_pos * 9 + square_pos],
+        Square::None => 0,
+    }
+}
+
+/// Returns the key for the player to move.
+#[inline]
+pub fn player(player: Square) -> u64 {
+    match player {
+        Square::X => KEYS[X_TO_MOVE],
+        _ => 0,
+    }
+}
+
+/// Returns the key for the forced board.
+#[inline]
+pub fn current_board(current_board: Option<usize>) -> u64 {
+    match current_board {
+        Some(board_pos) => KEYS[FORCED_BOARD_OFFSET + board_pos],
+        None => 0,
+    }
+}
+
+/// Computes the hash of the game from scratch.
+pub fn hash(game: &Game) -> u64 {
+    let mut hash = player(game.current_player()) ^ current_board(game.current_board());
+    for board_pos in 0..9 {
+        for (square_pos, &s) in game[board_pos].grid().iter().enumerate() {
+            hash ^= square(board_pos, square_pos, s);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zobrist;
+    use crate::Game;
+    use crate::PossibleMoves;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::collections::HashMap;
+
+    #[test]
+    fn transposition() {
+        let mut a = Game::default();
+        for (board_pos, square_pos) in [(0, 1), (1, 0), (0, 2), (2, 0)] {
+            a.play(board_pos, square_pos).unwrap();
+        }
+
+        let mut b = Game::default();
+        for (board_pos, square_pos) in [(0, 2), (2, 0), (0, 1), (1, 0)] {
+            b.play(board_pos, square_pos).unwrap();
+        }
+
+        assert_eq!(a.to_notation(), b.to_notation());
+        assert_eq!(a.hash(), b.hash());
+        assert_ne!(a.hash(), Game::default().hash());
+    }
+
+    #[test]
+    fn incremental() {
+        let r = &mut ChaCha8Rng::seed_from_u64(0);
+
+        // Different positions should (almost certainly) have different hashes.
+        let mut seen = HashMap::new();
+
+        for _ in 0..100 {
+            let mut g = Game::default();
+            let mut hashes = vec![g.hash()];
+
+            while g.playable() {
+                let board_pos = g.choose(r).unwrap();
+                let square_pos = g[board_pos].choose(r).unwrap();
+                g.play(board_pos, square_pos).unwrap();
+
+                assert_eq!(g.hash(), zobrist::hash(&g));
+                assert_eq!(
+                    *seen.entry(g.hash()).or_insert(g.to_notation()),
+                    g.to_notation()
+                );
+                hashes.push(g.hash());
+            }
+
+            // Undo restores the hash.
+            hashes.pop();
+            while let Some(hash) = hashes.pop() {
+                g.undo().unwrap();
+                assert_eq!(g.hash(), hash);
+            }
+        }
+    }
+}
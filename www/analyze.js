@@ -0,0 +1,49 @@
+import AnalysisWorker from "worker-loader!./worker.js";
+
+const worker = new AnalysisWorker();
+const pending = new Map();
+let next_id = 0;
+
+worker.onmessage = (e) => {
+	const { id, error } = e.data;
+	const request = pending.get(id);
+	if (!request) {
+		return;
+	}
+	pending.delete(id);
+
+	if (error) {
+		request.reject(new Error(error));
+	} else {
+		request.resolve(e.data);
+	}
+};
+
+// Rejects every request waiting on the worker, as it won't be answered.
+const rejectAll = (error) => {
+	for (const { reject } of pending.values()) {
+		reject(error);
+	}
+	pending.clear();
+};
+
+worker.onerror = (e) => {
+	rejectAll(new Error(e.message || "Analysis worker failed"));
+};
+
+worker.onmessageerror = () => {
+	rejectAll(new Error("Analysis worker sent a message that couldn't be read"));
+};
+
+// Analyses the game in a Web Worker, resolving to the best move, the number of
+// playouts, and the Stats of each square, indexed by board_pos * 9 + square_pos.
+//
+// Options are all optional: time_ms, playouts, seed, exploration, and policy
+// (a Policy from the wasm module).
+export const analyze = (game, options = {}) => {
+	return new Promise((resolve, reject) => {
+		const id = next_id++;
+		pending.set(id, { resolve, reject });
+		worker.postMessage({ id, game: game.to_notation(), options });
+	});
+};
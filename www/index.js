@@ -1,22 +1,41 @@
-import { Game, Square, RandomAI, MonteCarloAI } from "ultimate-tic-tac-toe";
+import { Game, GameResult, Square, RandomAI, MonteCarloAI, Search, AiOptions } from "ultimate-tic-tac-toe";
 
 //const ai = new RandomAI();
-const ai_assist = new MonteCarloAI();
 
 const game = new Game();
-let best;
 
-const calculate_probabilies = () => {
-	// Do this in the background.
-	setTimeout(() => {
-		const turns = game.turns();
-		best = ai_assist.choose(game);
+// Analyse each position for up to a minute, or until the search tree is full.
+const options = new AiOptions();
+options.time_ms = 60 * 1000;
+const ai_assist = Search.with_options(game, options);
+options.free();
+
+// How long to search for in each animation frame, leaving time to draw.
+const SEARCH_MS = 10;
+
+// What's currently drawn, so the page is only updated when it changes.
+let best = null;
+let drawnRuns = 0;
+let analysing = false;
+
+// Search a little every frame, so the page stays responsive while the
+// probabilities improve, until the search is done.
+const analyse = () => {
+	if (ai_assist.done()) {
+		analysing = false;
+		return;
+	}
 
-		// Only update if we are still on this turn.
-		if (game.turns() == turn) {
-			drawBoard(game);
-		}
-	}, 0);
+	ai_assist.step_for(SEARCH_MS);
+	drawAnalysis();
+	requestAnimationFrame(analyse);
+}
+
+const startAnalysis = () => {
+	if (!analysing) {
+		analysing = true;
+		requestAnimationFrame(analyse);
+	}
 }
 
 const play = (board, square) => {
@@ -27,8 +46,12 @@ const play = (board, square) => {
 		return;
 	}
 
+	ai_assist.set_game(game);
+	best = null;
+	drawnRuns = 0;
 	drawBoard(game);
-	calculate_probabilies();
+	drawAnalysis();
+	startAnalysis();
 
 /*
 	if (game.current_turn() == Square.X) {
@@ -54,14 +77,37 @@ const squareName = (square) => {
 	return "";
 }
 
+// The squares are created once, and updated in place, so clicks aren't lost
+// by replacing the element under the mouse.
+const megaBoard = document.querySelector("#mega-board");
+const boards = [];
+const squares = [];
+for (let i = 0; i < 9; i++) {
+	const board = document.createElement("div");
+	board.classList.add("board");
+	squares.push([]);
+
+	for (let j = 0; j < 9; j++) {
+		const square = document.createElement("div");
+		square.classList.add("square");
+		square.addEventListener("click", () => {
+			play(i, j);
+		});
+
+		board.appendChild(square);
+		squares[i].push(square);
+	}
+
+	megaBoard.appendChild(board);
+	boards.push(board);
+}
+
 const drawBoard = (game) => {
 	const current_player = game.current_player();
-	const current_board = game.current_board();
 
 	const turn = document.querySelector("#turn");
 	turn.innerHTML = squareName(current_player) + "'s turn";
 
-	const megaBoard = document.querySelector("#mega-board");
 	switch (current_player) {
 		case Square.O: {
 			megaBoard.setAttribute("class", "blue");
@@ -73,60 +119,34 @@ const drawBoard = (game) => {
 		}
 	}
 
-	let boards = [];
 	for (let i = 0; i < 9; i++) {
 		const b = game.board(i);
-		let board;
-
-		board = document.createElement("div");
-		board.classList.add("board");
+		const board = boards[i];
 
-		const current = (current_board === i || (current_board === undefined && b.playable()));
-		if (current) {
-			board.classList.add("current");
+		let current = false;
+		for (let j = 0; j < 9; j++) {
+			current = current || game.is_legal(i, j);
 		}
+		board.classList.toggle("current", current);
 
 		for (let j = 0; j < 9; j++) {
 			const s = b.square(j);
+			const square = squares[i][j];
 
-			const square = document.createElement("div");
-			square.classList.add("square");
+			square.classList.remove("available", "best", "won");
+			square.innerHTML = "";
 
-			if (current && s == Square.None) {
+			if (game.is_legal(i, j)) {
 				square.classList.add("available");
-
-				if (ai_assist.runs() > 0) {
-					let stats = ai_assist.stats(i, j);
-					let runs = ai_assist.runs();
-
-					let win_p = stats.wins / stats.totals;
-					let lose_p = stats.loses / stats.totals;
-					let draw_p = (stats.totals - stats.wins - stats.loses) / stats.totals;
-
-					if (i == best.board_pos && j == best.square_pos) {
-						square.classList.add("best");
-					}
- 
-					square.innerHTML = 
-						"w: " + (win_p * 100).toFixed(2) + "%<br/>" +
-						"l: " + (lose_p * 100).toFixed(2) + "%<br/>" +
-						"d: " + (draw_p * 100).toFixed(2) + "%";
-				}
 			} else if (s != Square.None) {
 				square.classList.add("won");
 
 				square.innerHTML = squareName(s);
 			}
-
-			
-			square.addEventListener("click", () => {
-				play(i, j);
-			});
-
-			board.appendChild(square);
 		}
-	
+
 		// Add a board winner overlay
+		board.querySelector(".winner")?.remove();
 		let winner = b.winner();
 		if (winner !== Square.None) {
 			let w = document.createElement("div");
@@ -141,20 +161,20 @@ const drawBoard = (game) => {
 			board.appendChild(w);
 		}
 
-		boards.push(board);
+		b.free();
 	}
 
 	// Add a megagrid winner overlay
-	if (!game.playable()) {
-		const winner = game.winner();
-
+	megaBoard.querySelector(".mega-winner")?.remove();
+	const result = game.result();
+	if (result != GameResult.InProgress) {
 		let w = document.createElement("div");
 		w.classList.add("mega-winner");
 
-		if (winner == Square.O) {
+		if (result == GameResult.OWins) {
 			w.classList.add("winner-blue");
 			w.innerHTML = "O";
-		} else if (winner == Square.X) {
+		} else if (result == GameResult.XWins) {
 			w.classList.add("winner-red");
 			w.innerHTML = "X";
 		} else {
@@ -162,38 +182,77 @@ const drawBoard = (game) => {
 			w.innerHTML = "Draw";
 		}
 
-		boards.push(w);
+		megaBoard.appendChild(w);
+	}
+}
+
+// Updates the probabilities and best move, if the search has moved on since
+// they were last drawn.
+const drawAnalysis = () => {
+	const runs = ai_assist.runs();
+	if (runs == drawnRuns) {
+		return;
 	}
+	drawnRuns = runs;
 
-	megaBoard.replaceChildren(...boards);
+	const p = ai_assist.current_best();
+	const newBest = p ? { board_pos: p.board_pos, square_pos: p.square_pos } : null;
+	p?.free();
 
+	if (best) {
+		squares[best.board_pos][best.square_pos].classList.remove("best");
+	}
+	if (newBest) {
+		squares[newBest.board_pos][newBest.square_pos].classList.add("best");
+	}
+	best = newBest;
 
-	if (ai_assist) {
-		const stats = ai_assist.totals();
-		if (stats.totals > 0) {
-			let o; let x;
-			let totals = stats.totals;
-			if (current_player == Square.O) {
-				o = stats.wins;
-				x = stats.loses;
-			} else {
-				x = stats.wins;
-				o = stats.loses;		
+	for (let i = 0; i < 9; i++) {
+		for (let j = 0; j < 9; j++) {
+			if (!game.is_legal(i, j)) {
+				continue;
 			}
 
-			const div = document.querySelector("#ai-stats");
-			div.innerHTML =  
-				" O Win:" + ((o / totals) * 100).toFixed(1) + "%" +
-				" X Win:" + ((x / totals) * 100).toFixed(1) + "%" +
-				"  Draw:" + ((1 - (x+o) / totals) * 100).toFixed(1) + "%";
+			const stats = ai_assist.current_stats(i, j);
+			const totals = stats.totals;
+			const win_p = stats.wins / totals;
+			const lose_p = stats.loses / totals;
+			const draw_p = (totals - stats.wins - stats.loses) / totals;
+			stats.free();
+
+			if (totals > 0) {
+				squares[i][j].innerHTML =
+					"w: " + (win_p * 100).toFixed(2) + "%<br/>" +
+					"l: " + (lose_p * 100).toFixed(2) + "%<br/>" +
+					"d: " + (draw_p * 100).toFixed(2) + "%";
+			}
 		}
 	}
 
+	const stats = ai_assist.totals();
+	const totals = stats.totals;
+	let o; let x;
+	if (game.current_player() == Square.O) {
+		o = stats.wins;
+		x = stats.loses;
+	} else {
+		x = stats.wins;
+		o = stats.loses;
+	}
+	stats.free();
+
+	if (totals > 0) {
+		const div = document.querySelector("#ai-stats");
+		div.innerHTML =
+			" O Win:" + ((o / totals) * 100).toFixed(1) + "%" +
+			" X Win:" + ((x / totals) * 100).toFixed(1) + "%" +
+			"  Draw:" + ((1 - (x+o) / totals) * 100).toFixed(1) + "%";
+	}
 }
 
 
 drawBoard(game);
-calculate_probabilies();
+startAnalysis();
 
 // Play a few rounds (must be without AI)
 /*
@@ -6,6 +6,8 @@ module.exports = {
   output: {
     path: path.resolve(__dirname, "dist"),
     filename: "bootstrap.js",
+    // The worker loads chunks with import(), where there's no window.
+    globalObject: "self",
   },
   mode: "development",
   plugins: [
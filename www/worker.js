@@ -0,0 +1,13 @@
+// Runs the analysis off the main thread. The wasm must be imported
+// asynchronously, as in `bootstrap.js`, so messages wait for it to load.
+const wasm = import("ultimate-tic-tac-toe");
+
+self.onmessage = async (e) => {
+	try {
+		const { analyze_message } = await wasm;
+		self.postMessage(analyze_message(e.data));
+	} catch (err) {
+		// Such as the wasm failing to load, or panicking.
+		self.postMessage({ id: e.data.id, error: String(err) });
+	}
+};